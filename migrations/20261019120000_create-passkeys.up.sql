@@ -0,0 +1,12 @@
+CREATE TABLE passkeys (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	who person NOT NULL,
+	credential JSONB NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE passkey_ceremonies (
+	session_id INTEGER PRIMARY KEY REFERENCES sessions (id),
+	state JSONB NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL
+);
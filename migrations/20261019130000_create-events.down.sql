@@ -0,0 +1,3 @@
+DROP TABLE events;
+DROP FUNCTION events_append_only;
+DROP TYPE entity;
@@ -0,0 +1,25 @@
+CREATE TYPE entity AS ENUM ('Expense', 'Transfer', 'Session', 'Passkey');
+
+CREATE TABLE events (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	actor person,
+	session_id INTEGER REFERENCES sessions (id),
+
+	entity entity NOT NULL,
+	entity_id INTEGER NOT NULL,
+	old_state TEXT,
+	new_state TEXT NOT NULL,
+
+	meta JSONB NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE FUNCTION events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+	RAISE EXCEPTION 'events are append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_append_only
+BEFORE UPDATE OR DELETE ON events
+FOR EACH STATEMENT EXECUTE FUNCTION events_append_only();
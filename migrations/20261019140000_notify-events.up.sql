@@ -0,0 +1,18 @@
+CREATE FUNCTION events_notify() RETURNS TRIGGER AS $$
+BEGIN
+	PERFORM pg_notify('events', json_build_object(
+		'id', NEW.id,
+		'actor', NEW.actor,
+		'session_id', NEW.session_id,
+		'entity', NEW.entity,
+		'entity_id', NEW.entity_id,
+		'old_state', NEW.old_state,
+		'new_state', NEW.new_state
+	)::TEXT);
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_notify
+AFTER INSERT ON events
+FOR EACH ROW EXECUTE FUNCTION events_notify();
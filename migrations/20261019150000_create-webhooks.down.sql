@@ -0,0 +1,4 @@
+DROP TRIGGER events_webhooks ON events;
+DROP FUNCTION events_webhooks;
+DROP TABLE webhook_deliveries;
+DROP TABLE webhooks;
@@ -0,0 +1,50 @@
+CREATE TABLE webhooks (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	url TEXT NOT NULL,
+	secret TEXT NOT NULL,
+	filter TEXT[] NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE webhook_deliveries (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	webhook_id INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+	payload JSONB NOT NULL,
+
+	attempts INTEGER NOT NULL DEFAULT 0,
+	last_error TEXT,
+	next_attempt_at TIMESTAMPTZ NOT NULL,
+	delivered_at TIMESTAMPTZ,
+
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX webhook_deliveries_pending
+ON webhook_deliveries (next_attempt_at)
+WHERE delivered_at IS NULL;
+
+-- A filter entry matches either an entity ("Expense") or a transition ("Expense.Confirmed").
+-- An empty filter matches everything.
+CREATE FUNCTION events_webhooks() RETURNS TRIGGER AS $$
+BEGIN
+	INSERT INTO webhook_deliveries (webhook_id, payload, next_attempt_at, created_at)
+	SELECT w.id, jsonb_build_object(
+		'id', NEW.id,
+		'actor', NEW.actor,
+		'entity', NEW.entity,
+		'entity_id', NEW.entity_id,
+		'old_state', NEW.old_state,
+		'new_state', NEW.new_state,
+		'created_at', NEW.created_at
+	), NOW(), NOW()
+	FROM webhooks w
+	WHERE cardinality(w.filter) = 0
+		OR NEW.entity::TEXT = ANY (w.filter)
+		OR NEW.entity::TEXT || '.' || NEW.new_state = ANY (w.filter);
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_webhooks
+AFTER INSERT ON events
+FOR EACH ROW EXECUTE FUNCTION events_webhooks();
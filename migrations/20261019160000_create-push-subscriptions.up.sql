@@ -0,0 +1,13 @@
+CREATE TABLE push_subscriptions (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	session_id INTEGER NOT NULL REFERENCES sessions (id),
+	endpoint TEXT NOT NULL UNIQUE,
+	p256dh TEXT NOT NULL,
+	auth TEXT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE push_sent (
+	event_id INTEGER PRIMARY KEY REFERENCES events (id),
+	created_at TIMESTAMPTZ NOT NULL
+);
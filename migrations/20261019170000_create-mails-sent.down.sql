@@ -0,0 +1 @@
+DROP TABLE mails_sent;
@@ -0,0 +1,7 @@
+CREATE TABLE mails_sent (
+	kind TEXT NOT NULL,
+	who person NOT NULL,
+	period TEXT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY (kind, who, period)
+);
@@ -0,0 +1 @@
+DROP TABLE pix_keys;
@@ -0,0 +1,7 @@
+CREATE TABLE pix_keys (
+	who person PRIMARY KEY,
+	key TEXT NOT NULL CHECK (length(key) <= 77),
+	name TEXT NOT NULL CHECK (length(name) <= 25),
+	city TEXT NOT NULL CHECK (length(city) <= 15),
+	updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1 @@
+DROP FUNCTION cents;
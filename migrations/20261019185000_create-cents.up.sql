@@ -0,0 +1,5 @@
+-- The whole cents in `amount`, read from its stored integer. Casting MONEY to NUMERIC
+-- instead scales it by the fractional digits of lc_monetary.
+CREATE FUNCTION cents(amount MONEY) RETURNS BIGINT AS $$
+	SELECT ('x' || encode(cash_send(amount), 'hex'))::BIT(64)::BIGINT
+$$ LANGUAGE SQL IMMUTABLE STRICT;
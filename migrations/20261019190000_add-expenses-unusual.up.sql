@@ -0,0 +1,2 @@
+ALTER TABLE expenses
+ADD COLUMN unusual BOOLEAN NOT NULL DEFAULT FALSE;
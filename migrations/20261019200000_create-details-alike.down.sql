@@ -0,0 +1,2 @@
+DROP FUNCTION details_alike;
+DROP FUNCTION detail_tokens;
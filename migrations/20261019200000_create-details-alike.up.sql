@@ -0,0 +1,16 @@
+CREATE FUNCTION detail_tokens(detail TEXT) RETURNS TEXT[] AS $$
+	SELECT ARRAY(
+		SELECT DISTINCT t
+		FROM regexp_split_to_table(LOWER(COALESCE(detail, '')), '[^[:alnum:]]+') t
+		WHERE LENGTH(t) >= 2
+	)
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- An empty detail can't tell anything apart, so it is alike anything.
+CREATE FUNCTION details_alike(a TEXT, b TEXT) RETURNS BOOLEAN AS $$
+	SELECT cardinality(ta) = 0
+		OR cardinality(tb) = 0
+		OR cardinality(ARRAY(SELECT unnest(ta) INTERSECT SELECT unnest(tb)))::FLOAT8
+			/ cardinality(ARRAY(SELECT unnest(ta) UNION SELECT unnest(tb))) >= 0.5
+	FROM (SELECT detail_tokens(a) as ta, detail_tokens(b) as tb) _
+$$ LANGUAGE SQL IMMUTABLE;
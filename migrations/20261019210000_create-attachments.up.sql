@@ -0,0 +1,21 @@
+-- Every stored blob, with how many attachments point at it. Uploads and deletes take its
+-- row lock, so a blob can't be removed from disk while it's being attached again.
+CREATE TABLE attachment_blobs (
+	sha256 TEXT PRIMARY KEY,
+	refs INTEGER NOT NULL CHECK (refs >= 0)
+);
+
+CREATE TABLE attachments (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	entity entity NOT NULL CHECK (entity IN ('Expense', 'Transfer')),
+	entity_id INTEGER NOT NULL,
+	creator person NOT NULL,
+	name TEXT NOT NULL CHECK (length(name) <= 255),
+	mime TEXT NOT NULL,
+	size INTEGER NOT NULL,
+	sha256 TEXT NOT NULL REFERENCES attachment_blobs (sha256),
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX attachments_entity ON attachments (entity, entity_id);
+CREATE INDEX attachments_sha256 ON attachments (sha256);
@@ -0,0 +1,21 @@
+CREATE TABLE receipts (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	access_key TEXT NOT NULL UNIQUE,
+	creator person NOT NULL,
+	emitter TEXT NOT NULL,
+	date DATE NOT NULL,
+	total MONEY NOT NULL,
+	expense_id INTEGER UNIQUE REFERENCES expenses (id),
+	created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE receipt_items (
+	receipt_id INTEGER NOT NULL REFERENCES receipts (id) ON DELETE CASCADE,
+	n INTEGER NOT NULL,
+	description TEXT NOT NULL,
+	quantity FLOAT8 NOT NULL,
+	unit TEXT NOT NULL,
+	unit_price MONEY NOT NULL,
+	total MONEY NOT NULL,
+	PRIMARY KEY (receipt_id, n)
+);
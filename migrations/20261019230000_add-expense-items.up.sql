@@ -0,0 +1,104 @@
+ALTER TABLE expenses
+DROP CONSTRAINT expenses_check1;
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE TEXT USING split::TEXT;
+
+DROP TYPE split;
+CREATE TYPE split AS ENUM (
+	'Proportional2to1',
+	'Proportional3to2',
+	'Arbitrary',
+	'Evenly',
+	'Itemized'
+);
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE split USING split::split;
+
+-- How much of `paid` the other person owes under each split. Itemized expenses only
+-- get the upper bound here; the sum of their items is checked by `expenses_itemized`.
+CREATE FUNCTION split_owed_valid(split split, payer person, paid MONEY, owed MONEY) RETURNS BOOLEAN AS $$
+	SELECT CASE split
+	WHEN 'Evenly' THEN owed = paid / 2
+	WHEN 'Arbitrary' THEN owed <= paid
+	WHEN 'Itemized' THEN owed <= paid
+	WHEN 'Proportional2to1' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 1 / 3
+		WHEN 'Lu' THEN owed = paid * 2 / 3
+		END
+	WHEN 'Proportional3to2' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 2 / 5
+		WHEN 'Lu' THEN owed = paid * 3 / 5
+		END
+	END
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE expenses
+ADD CONSTRAINT expenses_check1 CHECK (split_owed_valid(split, payer, paid, owed));
+
+ALTER TABLE expenses
+ADD CONSTRAINT expenses_id_payer UNIQUE (id, payer);
+
+CREATE TABLE expense_items (
+	expense_id INTEGER NOT NULL,
+	n INTEGER NOT NULL,
+	payer person NOT NULL,
+	label label,
+	detail TEXT,
+	amount MONEY NOT NULL CHECK (amount >= 0::MONEY),
+	split split CHECK (split <> 'Itemized'),
+	only_for person,
+	owed MONEY NOT NULL,
+
+	PRIMARY KEY (expense_id, n),
+	FOREIGN KEY (expense_id, payer) REFERENCES expenses (id, payer) ON DELETE CASCADE,
+	CHECK (num_nulls(split, only_for) = 1),
+	CHECK (
+		CASE
+		WHEN only_for = payer THEN owed = 0::MONEY
+		WHEN only_for IS NOT NULL THEN owed = amount
+		ELSE split_owed_valid(split, payer, amount, owed)
+		END
+	)
+);
+
+CREATE FUNCTION expenses_itemized() RETURNS TRIGGER AS $$
+DECLARE
+	e expenses;
+	items_paid MONEY;
+	items_owed MONEY;
+BEGIN
+	IF TG_TABLE_NAME = 'expenses' THEN
+		SELECT * INTO e FROM expenses WHERE id = NEW.id;
+	ELSE
+		SELECT * INTO e FROM expenses WHERE id = NEW.expense_id;
+	END IF;
+
+	SELECT SUM(amount), SUM(owed) INTO items_paid, items_owed
+	FROM expense_items
+	WHERE expense_id = e.id;
+
+	IF e.split = 'Itemized' THEN
+		IF items_paid IS DISTINCT FROM e.paid OR items_owed IS DISTINCT FROM e.owed THEN
+			RAISE EXCEPTION 'itemized expense % does not add up to its items', e.id;
+		END IF;
+	ELSIF items_paid IS NOT NULL THEN
+		RAISE EXCEPTION 'expense % has items but is not itemized', e.id;
+	END IF;
+
+	RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER expenses_itemized
+AFTER INSERT OR UPDATE OF split, paid, owed ON expenses
+DEFERRABLE INITIALLY DEFERRED
+FOR EACH ROW EXECUTE FUNCTION expenses_itemized();
+
+CREATE CONSTRAINT TRIGGER expense_items_itemized
+AFTER INSERT OR UPDATE ON expense_items
+DEFERRABLE INITIALLY DEFERRED
+FOR EACH ROW EXECUTE FUNCTION expenses_itemized();
@@ -0,0 +1,108 @@
+CREATE OR REPLACE FUNCTION events_webhooks() RETURNS TRIGGER AS $$
+BEGIN
+	INSERT INTO webhook_deliveries (webhook_id, payload, next_attempt_at, created_at)
+	SELECT w.id, jsonb_build_object(
+		'id', NEW.id,
+		'actor', NEW.actor,
+		'entity', NEW.entity,
+		'entity_id', NEW.entity_id,
+		'old_state', NEW.old_state,
+		'new_state', NEW.new_state,
+		'created_at', NEW.created_at
+	), NOW(), NOW()
+	FROM webhooks w
+	WHERE cardinality(w.filter) = 0
+		OR NEW.entity::TEXT = ANY (w.filter)
+		OR NEW.entity::TEXT || '.' || NEW.new_state = ANY (w.filter);
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION events_notify() RETURNS TRIGGER AS $$
+BEGIN
+	PERFORM pg_notify('events', json_build_object(
+		'id', NEW.id,
+		'actor', NEW.actor,
+		'session_id', NEW.session_id,
+		'entity', NEW.entity,
+		'entity_id', NEW.entity_id,
+		'old_state', NEW.old_state,
+		'new_state', NEW.new_state
+	)::TEXT);
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE expenses
+DROP CONSTRAINT expenses_personal,
+DROP CONSTRAINT expenses_check1;
+
+ALTER TABLE expense_items
+DROP CONSTRAINT expense_items_check1,
+DROP CONSTRAINT expense_items_split_check;
+
+DROP TRIGGER expenses_itemized ON expenses;
+DROP FUNCTION split_owed_valid;
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE TEXT USING split::TEXT;
+
+ALTER TABLE expense_items
+ALTER COLUMN split
+TYPE TEXT USING split::TEXT;
+
+UPDATE expenses
+SET split = 'Arbitrary'
+WHERE split = 'Personal';
+
+DROP TYPE split;
+CREATE TYPE split AS ENUM (
+	'Proportional2to1',
+	'Proportional3to2',
+	'Arbitrary',
+	'Evenly',
+	'Itemized'
+);
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE split USING split::split;
+
+ALTER TABLE expense_items
+ALTER COLUMN split
+TYPE split USING split::split;
+
+CREATE FUNCTION split_owed_valid(split split, payer person, paid MONEY, owed MONEY) RETURNS BOOLEAN AS $$
+	SELECT CASE split
+	WHEN 'Evenly' THEN owed = paid / 2
+	WHEN 'Arbitrary' THEN owed <= paid
+	WHEN 'Itemized' THEN owed <= paid
+	WHEN 'Proportional2to1' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 1 / 3
+		WHEN 'Lu' THEN owed = paid * 2 / 3
+		END
+	WHEN 'Proportional3to2' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 2 / 5
+		WHEN 'Lu' THEN owed = paid * 3 / 5
+		END
+	END
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE expenses
+ADD CONSTRAINT expenses_check1 CHECK (split_owed_valid(split, payer, paid, owed));
+
+ALTER TABLE expense_items
+ADD CONSTRAINT expense_items_split_check CHECK (split <> 'Itemized'),
+ADD CONSTRAINT expense_items_check1 CHECK (
+	CASE
+	WHEN only_for = payer THEN owed = 0::MONEY
+	WHEN only_for IS NOT NULL THEN owed = amount
+	ELSE split_owed_valid(split, payer, amount, owed)
+	END
+);
+
+CREATE CONSTRAINT TRIGGER expenses_itemized
+AFTER INSERT OR UPDATE OF split, paid, owed ON expenses
+DEFERRABLE INITIALLY DEFERRED
+FOR EACH ROW EXECUTE FUNCTION expenses_itemized();
@@ -0,0 +1,89 @@
+CREATE TABLE incomes (
+	who person NOT NULL,
+	month DATE NOT NULL CHECK (EXTRACT(DAY FROM month) = 1),
+	amount MONEY NOT NULL CHECK (amount >= 0::MONEY),
+	updated_at TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY (who, month)
+);
+
+ALTER TABLE expenses
+DROP CONSTRAINT expenses_personal,
+DROP CONSTRAINT expenses_check1;
+
+ALTER TABLE expense_items
+DROP CONSTRAINT expense_items_check1,
+DROP CONSTRAINT expense_items_split_check;
+
+DROP TRIGGER expenses_itemized ON expenses;
+DROP FUNCTION split_owed_valid;
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE TEXT USING split::TEXT;
+
+ALTER TABLE expense_items
+ALTER COLUMN split
+TYPE TEXT USING split::TEXT;
+
+DROP TYPE split;
+CREATE TYPE split AS ENUM (
+	'Proportional2to1',
+	'Proportional3to2',
+	'ProportionalIncome',
+	'Arbitrary',
+	'Evenly',
+	'Itemized',
+	'Personal'
+);
+
+ALTER TABLE expenses
+ALTER COLUMN split
+TYPE split USING split::split;
+
+ALTER TABLE expense_items
+ALTER COLUMN split
+TYPE split USING split::split;
+
+-- The share of `paid` owed by whoever didn't pay, as incomes stood on the expense date.
+ALTER TABLE expenses
+ADD COLUMN ratio NUMERIC(7, 6) CHECK (ratio BETWEEN 0 AND 1),
+ADD CONSTRAINT expenses_ratio CHECK ((split = 'ProportionalIncome') = (ratio IS NOT NULL));
+
+CREATE FUNCTION split_owed_valid(split split, payer person, paid MONEY, owed MONEY, ratio NUMERIC) RETURNS BOOLEAN AS $$
+	SELECT CASE split
+	WHEN 'Evenly' THEN owed = paid / 2
+	WHEN 'Arbitrary' THEN owed <= paid
+	WHEN 'Itemized' THEN owed <= paid
+	WHEN 'Personal' THEN owed = 0::MONEY
+	WHEN 'ProportionalIncome' THEN cents(owed) = TRUNC(cents(paid) * ratio)
+	WHEN 'Proportional2to1' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 1 / 3
+		WHEN 'Lu' THEN owed = paid * 2 / 3
+		END
+	WHEN 'Proportional3to2' THEN CASE payer
+		WHEN 'Ale' THEN owed = paid * 2 / 5
+		WHEN 'Lu' THEN owed = paid * 3 / 5
+		END
+	END
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE expenses
+ADD CONSTRAINT expenses_check1 CHECK (split_owed_valid(split, payer, paid, owed, ratio)),
+ADD CONSTRAINT expenses_personal CHECK (
+	split <> 'Personal' OR (payer = creator AND refused_at IS NULL AND confirmed_at IS NOT NULL)
+);
+
+ALTER TABLE expense_items
+ADD CONSTRAINT expense_items_split_check CHECK (split NOT IN ('Itemized', 'Personal', 'ProportionalIncome')),
+ADD CONSTRAINT expense_items_check1 CHECK (
+	CASE
+	WHEN only_for = payer THEN owed = 0::MONEY
+	WHEN only_for IS NOT NULL THEN owed = amount
+	ELSE split_owed_valid(split, payer, amount, owed, NULL)
+	END
+);
+
+CREATE CONSTRAINT TRIGGER expenses_itemized
+AFTER INSERT OR UPDATE OF split, paid, owed ON expenses
+DEFERRABLE INITIALLY DEFERRED
+FOR EACH ROW EXECUTE FUNCTION expenses_itemized();
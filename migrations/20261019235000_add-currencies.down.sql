@@ -0,0 +1,16 @@
+-- Foreign expenses and transfers are kept, as the BRL they were converted into.
+ALTER TABLE transfers
+DROP CONSTRAINT transfers_currency,
+DROP COLUMN exchange_rate,
+DROP COLUMN original_amount,
+DROP COLUMN currency;
+
+ALTER TABLE expenses
+DROP CONSTRAINT expenses_currency,
+DROP COLUMN exchange_rate,
+DROP COLUMN original_owed,
+DROP COLUMN original_paid,
+DROP COLUMN currency;
+
+DROP FUNCTION rate_for;
+DROP TABLE rates;
@@ -0,0 +1,43 @@
+-- How many BRL one unit of `currency` was worth on `day`.
+CREATE TABLE rates (
+	currency TEXT NOT NULL CHECK (currency ~ '^[A-Z]{3}$' AND currency <> 'BRL'),
+	day DATE NOT NULL,
+	rate NUMERIC(18, 8) NOT NULL CHECK (rate > 0),
+	updated_at TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY (currency, day)
+);
+
+-- The latest rate up to `day`, as long as it's no more than a week older.
+CREATE FUNCTION rate_for(currency TEXT, day DATE) RETURNS NUMERIC AS $$
+	SELECT r.rate
+	FROM rates r
+	WHERE r.currency = $1 AND r.day BETWEEN $2 - 7 AND $2
+	ORDER BY r.day DESC
+	LIMIT 1
+$$ LANGUAGE SQL STABLE;
+
+-- Original amounts are cents of `currency`; `paid`, `owed` and `amount` stay in BRL,
+-- converted when submitted and again when confirmed, with `exchange_rate` as used last.
+ALTER TABLE expenses
+ADD COLUMN currency TEXT NOT NULL DEFAULT 'BRL' CHECK (currency ~ '^[A-Z]{3}$'),
+ADD COLUMN original_paid BIGINT CHECK (original_paid >= 0),
+ADD COLUMN original_owed BIGINT CHECK (original_owed BETWEEN 0 AND original_paid),
+ADD COLUMN exchange_rate NUMERIC(18, 8) CHECK (exchange_rate > 0),
+ADD CONSTRAINT expenses_currency CHECK (
+	CASE currency
+	WHEN 'BRL' THEN original_paid IS NULL AND original_owed IS NULL AND exchange_rate IS NULL
+	ELSE original_paid IS NOT NULL AND original_owed IS NOT NULL AND exchange_rate IS NOT NULL
+		AND split <> 'Itemized'
+	END
+);
+
+ALTER TABLE transfers
+ADD COLUMN currency TEXT NOT NULL DEFAULT 'BRL' CHECK (currency ~ '^[A-Z]{3}$'),
+ADD COLUMN original_amount BIGINT CHECK (original_amount >= 0),
+ADD COLUMN exchange_rate NUMERIC(18, 8) CHECK (exchange_rate > 0),
+ADD CONSTRAINT transfers_currency CHECK (
+	CASE currency
+	WHEN 'BRL' THEN original_amount IS NULL AND exchange_rate IS NULL
+	ELSE original_amount IS NOT NULL AND exchange_rate IS NOT NULL
+	END
+);
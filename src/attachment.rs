@@ -0,0 +1,103 @@
+use crate::env::Env;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Content-addressed file store: each blob lives at `<dir>/<first two hex>/<sha256>`,
+/// so backing up the directory next to the database is enough to keep every receipt.
+#[derive(Clone)]
+pub struct Store {
+    dir: PathBuf,
+    pub max_bytes: usize,
+}
+
+pub async fn init(env: &Env) -> anyhow::Result<Store> {
+    let dir = PathBuf::from(&env.attachments_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    Ok(Store {
+        dir,
+        max_bytes: env.attachments_max_bytes,
+    })
+}
+
+impl Store {
+    pub fn path(&self, sha256: &str) -> PathBuf {
+        self.dir.join(&sha256[..2]).join(sha256)
+    }
+
+    /// Writes through a temporary file, so a blob is either complete or missing.
+    /// The blob's row in `attachment_blobs` has to be held meanwhile, see `remove`.
+    pub async fn put(&self, sha256: &str, bytes: &[u8]) -> std::io::Result<()> {
+        let path = self.path(sha256);
+
+        if tokio::fs::metadata(&path).await.is_ok() {
+            return Ok(());
+        }
+
+        tokio::fs::create_dir_all(&self.dir.join(&sha256[..2])).await?;
+
+        let tmp = path.with_extension(format!("tmp{}", rand::random::<u32>()));
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    pub async fn get(&self, sha256: &str) -> std::io::Result<Vec<u8>> {
+        tokio::fs::read(self.path(sha256)).await
+    }
+
+    /// Only for blobs nothing points at anymore, while their row is held so that
+    /// no upload of the same content can slip in between.
+    pub async fn remove(&self, sha256: &str) -> std::io::Result<()> {
+        match tokio::fs::remove_file(self.path(sha256)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// One ustar entry: the 512-byte header, the contents, and the padding to the next block.
+pub fn tar_entry(path: &str, bytes: &[u8]) -> Vec<u8> {
+    let mut header = [0u8; 512];
+
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[108..115].copy_from_slice(b"0000000");
+    header[116..123].copy_from_slice(b"0000000");
+    header[124..135].copy_from_slice(format!("{:011o}", bytes.len()).as_bytes());
+    header[136..147].copy_from_slice(b"00000000000");
+    header[148..156].copy_from_slice(b"        ");
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    let checksum = header.iter().map(|&b| b as u32).sum::<u32>();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+    let mut entry = Vec::with_capacity(512 + bytes.len() + 511);
+    entry.extend_from_slice(&header);
+    entry.extend_from_slice(bytes);
+    entry.resize(entry.len() + (512 - bytes.len() % 512) % 512, 0);
+    entry
+}
+
+/// Two zero blocks close a tar archive.
+pub const TAR_END: [u8; 1024] = [0; 1024];
+
+/// Tells the type from the leading bytes, whatever the client claimed it to be.
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [_, _, _, _, b'f', b't', b'y', b'p', b'h', b'e', b'i', b'c', ..] => Some("image/heic"),
+        [b'%', b'P', b'D', b'F', b'-', ..] => Some("application/pdf"),
+        _ => None,
+    }
+}
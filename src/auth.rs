@@ -1,20 +1,205 @@
-use crate::{env::Env, queries::Person};
+use crate::{env::Env, limit::TrustedProxies, queries::Person};
+use anyhow::Context;
 use axum::{
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    extract::{ConnectInfo, FromRef, FromRequestParts, State},
+    http::{
+        header::{SET_COOKIE, USER_AGENT},
+        request::Parts,
+        HeaderMap, HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
 use axum_extra::extract::{
     cookie::{Cookie, Key, SameSite},
-    PrivateCookieJar,
+    CookieJar, PrivateCookieJar,
 };
+use serde::Serialize;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
 use time::{Duration, OffsetDateTime};
+use webauthn_rs::{
+    prelude::{Url, Uuid},
+    Webauthn, WebauthnBuilder,
+};
 
 pub fn key(env: &Env) -> Key {
     axum_extra::extract::cookie::Key::from(env.secret.as_bytes())
 }
 
+#[derive(Clone)]
+pub struct RetiredKeys(Arc<[Key]>);
+
+pub fn retired_keys(env: &Env) -> RetiredKeys {
+    RetiredKeys(
+        env.secrets_retired
+            .iter()
+            .map(|s| Key::from(s.as_bytes()))
+            .collect(),
+    )
+}
+
+/// The relying party is the frontend, so passkeys are scoped to the allowed origin.
+/// Origins that can't be a relying party (like bare IPs) leave passkeys disabled.
+pub fn webauthn(env: &Env) -> Option<Arc<Webauthn>> {
+    let build = || -> anyhow::Result<Webauthn> {
+        let origin = Url::parse(env.allow_origin.to_str()?)?;
+        let rp_id = origin.domain().context("Origin without domain")?;
+
+        Ok(WebauthnBuilder::new(rp_id, &origin)?
+            .rp_name("expensas")
+            .build()?)
+    };
+
+    match build() {
+        Ok(webauthn) => Some(Arc::new(webauthn)),
+        Err(e) => {
+            tracing::warn!("Passkeys disabled: {e:?}");
+            None
+        }
+    }
+}
+
+/// Passkeys belong to the person rather than to a device, so each one gets a fixed user handle.
+pub fn user_handle(who: Person) -> (Uuid, &'static str) {
+    match who {
+        Person::Ale => (Uuid::from_u128(1), "ale"),
+        Person::Lu => (Uuid::from_u128(2), "lu"),
+    }
+}
+
+/// Finds a private cookie, trying the primary key first and then every retired one.
+/// The flag tells whether it was only readable with a retired key.
+fn lookup(
+    headers: &HeaderMap,
+    key: &Key,
+    retired: &RetiredKeys,
+    name: &str,
+) -> Option<(Cookie<'static>, bool)> {
+    PrivateCookieJar::from_headers(headers, key.clone())
+        .get(name)
+        .map(|c| (c, false))
+        .or_else(|| {
+            retired
+                .0
+                .iter()
+                .find_map(|k| PrivateCookieJar::from_headers(headers, k.clone()).get(name))
+                .map(|c| (c, true))
+        })
+}
+
+fn cookie<S>(parts: &Parts, state: &S, name: &str) -> Option<Cookie<'static>>
+where
+    Key: FromRef<S>,
+    RetiredKeys: FromRef<S>,
+{
+    lookup(
+        &parts.headers,
+        &Key::from_ref(state),
+        &RetiredKeys::from_ref(state),
+        name,
+    )
+    .map(|(c, _)| c)
+}
+
+/// Re-issues with the primary key every cookie that could only be read with a retired one,
+/// unless the handler already set that cookie itself.
+pub async fn rotate<B>(
+    State(key): State<Key>,
+    State(retired): State<RetiredKeys>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let stale = [COOKIE_SESSION, COOKIE_SESSION_ASK]
+        .into_iter()
+        .filter_map(|name| match lookup(req.headers(), &key, &retired, name) {
+            Some((cookie, true)) => reissue(cookie),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let res = next.run(req).await;
+
+    let jar = stale
+        .into_iter()
+        .filter(|c| {
+            !res.headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .any(|h| h.starts_with(&format!("{}=", c.name())))
+        })
+        .fold(PrivateCookieJar::new(key), PrivateCookieJar::add);
+
+    (jar, res).into_response()
+}
+
+/// Double-submit CSRF protection. Any POST carrying a session cookie must echo the
+/// `csrf` cookie in the `x-csrf-token` header, which a cross-origin page can't read.
+/// Requests arriving without the cookie get a fresh one issued with the response.
+/// The frontend lives on another origin and can't read the cookie either, so every
+/// response also carries the token in that header, exposed by CORS to the allowed origin only.
+pub async fn csrf<B>(req: Request<B>, next: Next<B>) -> Response {
+    let cookies = CookieJar::from_headers(req.headers());
+    let token = cookies.get(COOKIE_CSRF).map(|c| c.value().to_owned());
+
+    if req.method() == Method::POST
+        && (cookies.get(COOKIE_SESSION).is_some() || cookies.get(COOKIE_SESSION_ASK).is_some())
+    {
+        let header = req.headers().get(HEADER_CSRF).and_then(|h| h.to_str().ok());
+
+        match (&token, header) {
+            (Some(token), Some(header)) if token == header => {}
+            _ => return StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+
+    let res = next.run(req).await;
+
+    let (token, mut res) = match token {
+        Some(token) => (token, res),
+        None => {
+            let cookie = csrf_cookie();
+            let token = cookie.value().to_owned();
+            (token, (CookieJar::new().add(cookie), res).into_response())
+        }
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&token) {
+        res.headers_mut().insert(HEADER_CSRF, value);
+    }
+
+    res
+}
+
+fn csrf_cookie() -> Cookie<'static> {
+    let token = rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    let mut cookie = Cookie::new(COOKIE_CSRF, token);
+    cookie.set_expires(OffsetDateTime::now_utc() + Duration::weeks(12));
+    cookie.set_same_site(SameSite::Strict);
+    cookie.set_path("/");
+    cookie
+}
+
+fn reissue(cookie: Cookie<'static>) -> Option<Cookie<'static>> {
+    match cookie.name() {
+        COOKIE_SESSION => Session::parse(cookie.value()).map(Cookie::from),
+        COOKIE_SESSION_ASK => SessionAsk::parse(cookie.value()).map(Cookie::from),
+        _ => None,
+    }
+}
+
 pub struct SessionAsk(pub i32);
 
+impl SessionAsk {
+    fn parse(value: &str) -> Option<Self> {
+        value.parse::<i32>().ok().map(SessionAsk)
+    }
+}
+
 #[axum::async_trait]
 impl FromRequestParts<crate::routes::State> for SessionAsk {
     type Rejection = StatusCode;
@@ -23,17 +208,9 @@ impl FromRequestParts<crate::routes::State> for SessionAsk {
         parts: &mut Parts,
         state: &crate::routes::State,
     ) -> Result<Self, Self::Rejection> {
-        let cookies =
-            PrivateCookieJar::<axum_extra::extract::cookie::Key>::from_request_parts(parts, state)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        let id = cookies
-            .get(COOKIE_SESSION_ASK)
-            .and_then(|c| c.value().parse::<i32>().ok())
-            .ok_or(StatusCode::BAD_REQUEST)?;
-
-        Ok(SessionAsk(id))
+        cookie(parts, state, COOKIE_SESSION_ASK)
+            .and_then(|c| SessionAsk::parse(c.value()))
+            .ok_or(StatusCode::BAD_REQUEST)
     }
 }
 
@@ -52,6 +229,24 @@ pub struct Session {
     pub id: i32,
 }
 
+impl Session {
+    fn parse(value: &str) -> Option<Self> {
+        value
+            .split_once('/')
+            .and_then(|(id, who)| match (id.parse::<i32>(), who) {
+                (Ok(id), "ale") => Some(Session {
+                    id,
+                    who: Person::Ale,
+                }),
+                (Ok(id), "lu") => Some(Session {
+                    id,
+                    who: Person::Lu,
+                }),
+                _ => None,
+            })
+    }
+}
+
 #[axum::async_trait]
 impl FromRequestParts<crate::routes::State> for Session {
     type Rejection = StatusCode;
@@ -60,23 +255,9 @@ impl FromRequestParts<crate::routes::State> for Session {
         parts: &mut Parts,
         state: &crate::routes::State,
     ) -> Result<Self, Self::Rejection> {
-        let cookies =
-            PrivateCookieJar::<axum_extra::extract::cookie::Key>::from_request_parts(parts, state)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        let (id, who) = cookies
-            .get(COOKIE_SESSION)
-            .as_ref()
-            .and_then(|c| c.value().split_once('/'))
-            .and_then(|(id, who)| match (id.parse::<i32>(), who) {
-                (Ok(id), "ale") => Some((id, Person::Ale)),
-                (Ok(id), "lu") => Some((id, Person::Lu)),
-                _ => None,
-            })
-            .ok_or(StatusCode::BAD_REQUEST)?;
-
-        Ok(Session { who, id })
+        cookie(parts, state, COOKIE_SESSION)
+            .and_then(|c| Session::parse(c.value()))
+            .ok_or(StatusCode::BAD_REQUEST)
     }
 }
 
@@ -98,5 +279,143 @@ impl From<Session> for Cookie<'static> {
     }
 }
 
+/// Request details recorded along with every audited action.
+#[derive(Serialize)]
+pub struct Meta {
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Meta
+where
+    TrustedProxies: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let proxies = TrustedProxies::from_ref(state);
+
+        Ok(Meta {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(peer)| {
+                    crate::limit::client_ip(&parts.headers, *peer, &proxies).to_string()
+                }),
+            user_agent: parts
+                .headers
+                .get(USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_owned),
+        })
+    }
+}
+
 const COOKIE_SESSION_ASK: &str = "ask";
 const COOKIE_SESSION: &str = "ses";
+const COOKIE_CSRF: &str = "csrf";
+pub const HEADER_CSRF: &str = "x-csrf-token";
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::test::{self, Client, ORIGIN};
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use serde_json::Value;
+
+    const EVIL: &str = "https://evil.example";
+
+    /// What a page on another site can send: the browser attaches the cookies,
+    /// but the page never got to see the token. CORS only ever names the allowed
+    /// origin, so the browser won't let that page read any response either.
+    fn forged(victim: &Client, token: Option<&str>) -> Request<Body> {
+        let cookies = victim
+            .cookies
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/transfer/submit")
+            .header(header::ORIGIN, EVIL)
+            .header(header::COOKIE, cookies)
+            .header(header::CONTENT_TYPE, "application/json");
+
+        if let Some(token) = token {
+            req = req.header(super::HEADER_CSRF, token);
+        }
+
+        req.body(Body::from(r#"{"date":"2026-10-19","amount":100}"#))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn rejects_cross_origin_posts(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let without = ale.send(forged(&ale, None)).await;
+        assert_eq!(without.status, StatusCode::FORBIDDEN);
+        assert_eq!(without.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN);
+
+        let guessed = ale.send(forged(&ale, Some("00"))).await;
+        assert_eq!(guessed.status, StatusCode::FORBIDDEN);
+
+        let transfers = sqlx::query_scalar!(r#"SELECT COUNT(1) as "count!" FROM transfers"#)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(transfers, 0);
+    }
+
+    #[sqlx::test]
+    async fn hands_the_token_to_the_allowed_origin_only(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let mut browser = Client::new(&app);
+
+        let reply = browser.get("/").await;
+        assert!(reply.headers.get(super::HEADER_CSRF).is_some());
+        assert_eq!(reply.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN);
+        assert!(reply.headers[header::ACCESS_CONTROL_EXPOSE_HEADERS]
+            .to_str()
+            .unwrap()
+            .contains(super::HEADER_CSRF));
+
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/transfer/submit")
+            .header(header::ORIGIN, EVIL)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, super::HEADER_CSRF)
+            .body(Body::empty())
+            .unwrap();
+        let preflight = browser.send(preflight).await;
+        assert_eq!(
+            preflight.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            ORIGIN
+        );
+    }
+
+    #[sqlx::test]
+    async fn accepts_the_echoed_token(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (_, mut lu) = test::sessions(&app).await;
+
+        let body = serde_json::json!({ "date": "2026-10-19", "amount": 100 });
+        assert_eq!(
+            lu.post("/transfer/submit", body).await.status,
+            StatusCode::OK
+        );
+
+        lu.csrf = None;
+        assert_eq!(
+            lu.post("/transfer/submit", Value::Null).await.status,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -1,12 +1,38 @@
 use anyhow::Context;
 use axum::http::HeaderValue;
-use std::{error::Error, net::SocketAddr, str::FromStr};
+use std::{
+    error::Error,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
 
 pub struct Env {
     pub allow_origin: HeaderValue,
     pub database_url: String,
     pub rest_socket: SocketAddr,
     pub secret: String,
+    pub secrets_retired: Vec<String>,
+    pub rate_limit: u32,
+    pub trusted_proxies: Vec<IpAddr>,
+    pub ask_cap: i64,
+    pub lockout_refusals: i64,
+    pub lockout_minutes: i32,
+    pub vapid_private_key: String,
+    pub vapid_subject: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_starttls: bool,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub mail_from: String,
+    pub mail_ale: String,
+    pub mail_lu: String,
+    pub auto_resolve: String,
+    pub auto_resolve_days: i32,
+    pub auto_resolve_warn_days: i32,
+    pub duplicate_days: i32,
+    pub attachments_dir: String,
+    pub attachments_max_bytes: usize,
 }
 
 #[cfg(not(debug_assertions))]
@@ -16,6 +42,28 @@ pub async fn init() -> anyhow::Result<Env> {
         database_url: read("DATABASE_URL")?,
         rest_socket: read("REST_SOCKET")?,
         secret: read("SECRET")?,
+        secrets_retired: list(read_or("SECRETS_RETIRED", String::new())?),
+        rate_limit: read_or("RATE_LIMIT", 10)?,
+        trusted_proxies: addresses(read_or("TRUSTED_PROXIES", String::new())?)?,
+        ask_cap: read_or("ASK_CAP", 5)?,
+        lockout_refusals: read_or("LOCKOUT_REFUSALS", 3)?,
+        lockout_minutes: read_or("LOCKOUT_MINUTES", 60)?,
+        vapid_private_key: read_or("VAPID_PRIVATE_KEY", String::new())?,
+        vapid_subject: read_or("VAPID_SUBJECT", String::new())?,
+        smtp_host: read_or("SMTP_HOST", String::new())?,
+        smtp_port: read_or("SMTP_PORT", 587)?,
+        smtp_starttls: read_or("SMTP_STARTTLS", true)?,
+        smtp_username: read_or("SMTP_USERNAME", String::new())?,
+        smtp_password: read_or("SMTP_PASSWORD", String::new())?,
+        mail_from: read_or("MAIL_FROM", String::new())?,
+        mail_ale: read_or("MAIL_ALE", String::new())?,
+        mail_lu: read_or("MAIL_LU", String::new())?,
+        auto_resolve: read_or("AUTO_RESOLVE", String::new())?,
+        auto_resolve_days: read_or("AUTO_RESOLVE_DAYS", 14)?,
+        auto_resolve_warn_days: read_or("AUTO_RESOLVE_WARN_DAYS", 3)?,
+        duplicate_days: read_or("DUPLICATE_DAYS", 3)?,
+        attachments_dir: read_or("ATTACHMENTS_DIR", String::from("attachments"))?,
+        attachments_max_bytes: read_or("ATTACHMENTS_MAX_BYTES", 10 * 1024 * 1024)?,
     })
 }
 
@@ -31,6 +79,20 @@ where
         .with_context(|| format!("Key unparsable: {key}"))
 }
 
+#[cfg(not(debug_assertions))]
+fn read_or<T, E>(key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr<Err = E>,
+    E: Error + Send + Sync + 'static,
+{
+    match std::env::var(key) {
+        Err(_) => Ok(default),
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("Key unparsable: {key}")),
+    }
+}
+
 #[cfg(debug_assertions)]
 pub async fn init() -> anyhow::Result<Env> {
     let file = tokio::fs::read_to_string(".env")
@@ -47,6 +109,28 @@ pub async fn init() -> anyhow::Result<Env> {
         database_url: read(&mut map, "DATABASE_URL")?,
         rest_socket: read(&mut map, "REST_SOCKET")?,
         secret: read(&mut map, "SECRET")?,
+        secrets_retired: list(read_or(&mut map, "SECRETS_RETIRED", String::new())?),
+        rate_limit: read_or(&mut map, "RATE_LIMIT", 10)?,
+        trusted_proxies: addresses(read_or(&mut map, "TRUSTED_PROXIES", String::new())?)?,
+        ask_cap: read_or(&mut map, "ASK_CAP", 5)?,
+        lockout_refusals: read_or(&mut map, "LOCKOUT_REFUSALS", 3)?,
+        lockout_minutes: read_or(&mut map, "LOCKOUT_MINUTES", 60)?,
+        vapid_private_key: read_or(&mut map, "VAPID_PRIVATE_KEY", String::new())?,
+        vapid_subject: read_or(&mut map, "VAPID_SUBJECT", String::new())?,
+        smtp_host: read_or(&mut map, "SMTP_HOST", String::new())?,
+        smtp_port: read_or(&mut map, "SMTP_PORT", 587)?,
+        smtp_starttls: read_or(&mut map, "SMTP_STARTTLS", true)?,
+        smtp_username: read_or(&mut map, "SMTP_USERNAME", String::new())?,
+        smtp_password: read_or(&mut map, "SMTP_PASSWORD", String::new())?,
+        mail_from: read_or(&mut map, "MAIL_FROM", String::new())?,
+        mail_ale: read_or(&mut map, "MAIL_ALE", String::new())?,
+        mail_lu: read_or(&mut map, "MAIL_LU", String::new())?,
+        auto_resolve: read_or(&mut map, "AUTO_RESOLVE", String::new())?,
+        auto_resolve_days: read_or(&mut map, "AUTO_RESOLVE_DAYS", 14)?,
+        auto_resolve_warn_days: read_or(&mut map, "AUTO_RESOLVE_WARN_DAYS", 3)?,
+        duplicate_days: read_or(&mut map, "DUPLICATE_DAYS", 3)?,
+        attachments_dir: read_or(&mut map, "ATTACHMENTS_DIR", String::from("attachments"))?,
+        attachments_max_bytes: read_or(&mut map, "ATTACHMENTS_MAX_BYTES", 10 * 1024 * 1024)?,
     })
 }
 
@@ -61,3 +145,71 @@ where
         .parse()
         .with_context(|| format!("Key unparsable: {key}"))
 }
+
+#[cfg(debug_assertions)]
+fn read_or<T, E>(
+    map: &mut std::collections::HashMap<&str, &str>,
+    key: &str,
+    default: T,
+) -> anyhow::Result<T>
+where
+    T: FromStr<Err = E>,
+    E: Error + Send + Sync + 'static,
+{
+    match map.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("Key unparsable: {key}")),
+    }
+}
+
+fn list(value: String) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn addresses(value: String) -> anyhow::Result<Vec<IpAddr>> {
+    list(value)
+        .iter()
+        .map(|a| a.parse().with_context(|| "Key unparsable: TRUSTED_PROXIES"))
+        .collect()
+}
+
+#[cfg(test)]
+pub fn test() -> Env {
+    Env {
+        allow_origin: HeaderValue::from_static("https://localhost"),
+        database_url: String::new(),
+        rest_socket: SocketAddr::from(([127, 0, 0, 1], 0)),
+        secret: "s".repeat(64),
+        secrets_retired: Vec::new(),
+        rate_limit: 1000,
+        trusted_proxies: Vec::new(),
+        ask_cap: 5,
+        lockout_refusals: 3,
+        lockout_minutes: 60,
+        vapid_private_key: String::new(),
+        vapid_subject: String::new(),
+        smtp_host: String::new(),
+        smtp_port: 587,
+        smtp_starttls: true,
+        smtp_username: String::new(),
+        smtp_password: String::new(),
+        mail_from: String::new(),
+        mail_ale: String::new(),
+        mail_lu: String::new(),
+        auto_resolve: String::new(),
+        auto_resolve_days: 14,
+        auto_resolve_warn_days: 3,
+        duplicate_days: 3,
+        attachments_dir: std::env::temp_dir()
+            .join(format!("expensas-test-{}", rand::random::<u64>()))
+            .to_string_lossy()
+            .into_owned(),
+        attachments_max_bytes: 1024 * 1024,
+    }
+}
@@ -0,0 +1,175 @@
+use crate::queries::{Person, Split};
+use std::{fmt, str::FromStr};
+
+const SCALE: i128 = 100_000_000;
+
+/// How many `BASE` one unit of a foreign currency is worth, exactly as `rates` keeps it:
+/// a positive decimal of up to ten integer and eight fractional digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate(i64);
+
+impl FromStr for Rate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+
+        if int.is_empty()
+            || int.len() > 10
+            || frac.len() > 8
+            || !(int.bytes().chain(frac.bytes())).all(|b| b.is_ascii_digit())
+        {
+            return Err(());
+        }
+
+        let frac = format!("{frac:0<8}");
+        let rate = int.parse::<i64>().map_err(|_| ())? * SCALE as i64
+            + frac.parse::<i64>().map_err(|_| ())?;
+
+        if rate == 0 {
+            return Err(());
+        }
+
+        Ok(Rate(rate))
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let int = self.0 / SCALE as i64;
+        let frac = format!("{:08}", self.0 % SCALE as i64);
+        match frac.trim_end_matches('0') {
+            "" => write!(f, "{int}"),
+            frac => write!(f, "{int}.{frac}"),
+        }
+    }
+}
+
+impl Rate {
+    /// Cents of the foreign currency in cents of `BASE`, rounded half up.
+    pub fn convert(self, cents: i64) -> i64 {
+        ((cents as i128 * self.0 as i128 + SCALE / 2).div_euclid(SCALE)) as i64
+    }
+}
+
+/// The part of `paid` a six-place `ratio` stands for, truncated like `split_owed_valid` does.
+pub fn share(paid: i64, ratio: f64) -> i64 {
+    (paid as i128 * (ratio * 1e6).round() as i128 / 1_000_000) as i64
+}
+
+/// What's owed in `BASE` once `paid` is: an arbitrary split converts what was asked for,
+/// since converting never makes it exceed `paid`, and the others are worked out again.
+pub fn owed(
+    split: Split,
+    payer: Person,
+    paid: i64,
+    arbitrary: i64,
+    ratio: Option<f64>,
+) -> Option<i64> {
+    match (split, ratio) {
+        (Split::ProportionalIncome, Some(ratio)) => Some(share(paid, ratio)),
+        (Split::Arbitrary, _) => split.owed(payer, paid, Some(arbitrary)),
+        (split, _) => split.owed(payer, paid, None),
+    }
+}
+
+/// Converts a pending foreign expense again as it gets confirmed, with the rates known by then.
+/// Nothing changes for `BASE` ones.
+pub async fn expense(db: &mut sqlx::PgConnection, id: i32) -> sqlx::Result<()> {
+    let Some(e) = crate::queries::expense::foreign(&mut *db, id).await? else {
+        return Ok(());
+    };
+
+    let Some(rate) = crate::queries::rate::rate_for(&mut *db, &e.currency, e.date).await? else {
+        return Ok(());
+    };
+
+    let paid = rate.convert(e.original_paid);
+    let Some(owed) = owed(
+        e.split,
+        e.payer,
+        paid,
+        rate.convert(e.original_owed),
+        e.ratio,
+    ) else {
+        return Ok(());
+    };
+
+    crate::queries::expense::convert(db, id, paid, owed, rate).await
+}
+
+/// Like `expense`, for transfers.
+pub async fn transfer(db: &mut sqlx::PgConnection, id: i32) -> sqlx::Result<()> {
+    let Some(t) = crate::queries::transfer::foreign(&mut *db, id).await? else {
+        return Ok(());
+    };
+
+    let Some(rate) = crate::queries::rate::rate_for(&mut *db, &t.currency, t.date).await? else {
+        return Ok(());
+    };
+
+    crate::queries::transfer::convert(db, id, rate.convert(t.original_amount), rate).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_rates_exact() {
+        let rate = "5.12345678".parse::<Rate>().unwrap();
+        assert_eq!(rate.to_string(), "5.12345678");
+        assert_eq!("5.10".parse::<Rate>().unwrap().to_string(), "5.1");
+        assert_eq!(
+            "0.00000001".parse::<Rate>().unwrap().to_string(),
+            "0.00000001"
+        );
+        assert_eq!("7".parse::<Rate>().unwrap().to_string(), "7");
+
+        for invalid in [
+            "",
+            ".5",
+            "0",
+            "0.000000001",
+            "-1",
+            "1e3",
+            "12345678901",
+            "1,5",
+        ] {
+            assert!(invalid.parse::<Rate>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn converts_to_the_nearest_cent() {
+        let rate = "5.12345678".parse::<Rate>().unwrap();
+        assert_eq!(rate.convert(100), 512);
+        assert_eq!(rate.convert(1000), 5123);
+        assert_eq!("0.005".parse::<Rate>().unwrap().convert(100), 1);
+        assert_eq!("0.004".parse::<Rate>().unwrap().convert(100), 0);
+    }
+
+    #[test]
+    fn works_owed_out_again() {
+        assert_eq!(share(1001, 0.250002), 250);
+        assert_eq!(
+            owed(Split::Arbitrary, Person::Ale, 500, 300, None),
+            Some(300)
+        );
+        assert_eq!(owed(Split::Evenly, Person::Ale, 501, 300, None), Some(250));
+        assert_eq!(
+            owed(Split::Proportional2to1, Person::Lu, 900, 0, None),
+            Some(600)
+        );
+        assert_eq!(
+            owed(
+                Split::ProportionalIncome,
+                Person::Lu,
+                1001,
+                0,
+                Some(0.250002)
+            ),
+            Some(250)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{env::Env, queries::event::Entity};
+use futures::TryFutureExt;
+use serde_json::json;
+use std::time::Duration;
+
+/// Policy for items nobody confirmed or refused in time.
+#[derive(Clone, Copy)]
+pub struct Grace {
+    pub confirm: bool,
+    pub days: i32,
+    pub warn_days: i32,
+}
+
+/// Starts resolving stale pending items in the background, when a policy is configured.
+pub fn init(env: &Env, db: sqlx::PgPool) -> anyhow::Result<Option<Grace>> {
+    let confirm = match env.auto_resolve.as_str() {
+        "" => return Ok(None),
+        "confirm" => true,
+        "refuse" => false,
+        other => anyhow::bail!("Key unparsable: AUTO_RESOLVE ({other})"),
+    };
+
+    let grace = Grace {
+        confirm,
+        days: env.auto_resolve_days,
+        warn_days: env.auto_resolve_warn_days,
+    };
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = expire(&db, grace).await {
+                tracing::error!("{e:?}");
+            }
+
+            tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        }
+    });
+
+    Ok(Some(grace))
+}
+
+/// Resolves everything past the grace period, recording each one as a system action.
+async fn expire(db: &sqlx::PgPool, grace: Grace) -> sqlx::Result<()> {
+    let state = if grace.confirm {
+        "Confirmed"
+    } else {
+        "Refused"
+    };
+    let meta = json!({ "system": "grace", "days": grace.days });
+
+    db.begin()
+        .and_then(|mut transaction| async move {
+            let expenses =
+                crate::queries::expense::expire(&mut transaction, grace.days, grace.confirm)
+                    .await?;
+            let transfers =
+                crate::queries::transfer::expire(&mut transaction, grace.days, grace.confirm)
+                    .await?;
+
+            if grace.confirm {
+                for &id in &expenses {
+                    crate::exchange::expense(&mut transaction, id).await?;
+                }
+                for &id in &transfers {
+                    crate::exchange::transfer(&mut transaction, id).await?;
+                }
+            }
+
+            let expired = expenses
+                .into_iter()
+                .map(|id| (Entity::Expense, id))
+                .chain(transfers.into_iter().map(|id| (Entity::Transfer, id)));
+
+            for (entity, id) in expired {
+                crate::queries::event::record(
+                    &mut transaction,
+                    None,
+                    None,
+                    entity,
+                    id,
+                    Some("Pending"),
+                    state,
+                    &meta,
+                )
+                .await?;
+            }
+
+            transaction.commit().await
+        })
+        .await
+}
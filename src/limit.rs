@@ -0,0 +1,161 @@
+use crate::{env::Env, queries::Person};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Fixed-window request counter, shared by every route it's layered on.
+pub struct RateLimiter {
+    max: u32,
+    window: Duration,
+    hits: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+pub fn rate_limiter(env: &Env) -> Arc<RateLimiter> {
+    Arc::new(RateLimiter {
+        max: env.rate_limit,
+        window: Duration::from_secs(60),
+        hits: Mutex::default(),
+    })
+}
+
+impl RateLimiter {
+    fn hit(&self, key: String) -> bool {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+
+        hits.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+
+        let (_, count) = hits.entry(key).or_insert((now, 0));
+        *count += 1;
+        *count <= self.max
+    }
+}
+
+/// Limits requests both per client address and, when the route names one, per person.
+pub async fn layer<B>(
+    State(limiter): State<Arc<RateLimiter>>,
+    State(proxies): State<TrustedProxies>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    who: Option<Path<Person>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let ip = client_ip(req.headers(), peer, &proxies);
+
+    let by_ip = limiter.hit(format!("ip/{ip}"));
+    let by_who = match who {
+        Some(Path(who)) => limiter.hit(format!("who/{who:?}")),
+        None => true,
+    };
+
+    if !(by_ip && by_who) {
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Peers allowed to say who they forward for, i.e. the reverse proxy in front of the service.
+#[derive(Clone)]
+pub struct TrustedProxies(Arc<[IpAddr]>);
+
+pub fn trusted_proxies(env: &Env) -> TrustedProxies {
+    TrustedProxies(env.trusted_proxies.as_slice().into())
+}
+
+/// Forwarded addresses are only believed while the hop that appended them is a trusted proxy,
+/// otherwise any client could pick its own address.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, proxies: &TrustedProxies) -> IpAddr {
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .collect::<Vec<_>>();
+
+    let mut ip = peer.ip();
+    for hop in forwarded.into_iter().rev() {
+        if !proxies.0.contains(&ip) {
+            break;
+        }
+
+        match hop.trim().parse() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+
+    ip
+}
+
+/// Limits on session asks that need the database to be enforced.
+#[derive(Clone, Copy)]
+pub struct Lockout {
+    pub ask_cap: i64,
+    pub refusals: i64,
+    pub minutes: i32,
+}
+
+pub fn lockout(env: &Env) -> Lockout {
+    Lockout {
+        ask_cap: env.ask_cap,
+        refusals: env.lockout_refusals,
+        minutes: env.lockout_minutes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_ip, TrustedProxies};
+    use axum::http::HeaderMap;
+    use std::net::{IpAddr, SocketAddr};
+
+    fn ip(headers: &[&str], peer: [u8; 4], proxies: &[[u8; 4]]) -> IpAddr {
+        let mut map = HeaderMap::new();
+        for h in headers {
+            map.append("x-forwarded-for", h.parse().unwrap());
+        }
+
+        let proxies = TrustedProxies(proxies.iter().map(|&p| IpAddr::from(p)).collect());
+        client_ip(&map, SocketAddr::from((peer, 4000)), &proxies)
+    }
+
+    #[test]
+    fn ignores_forwarding_from_untrusted_peers() {
+        assert_eq!(
+            ip(&["1.1.1.1"], [9, 9, 9, 9], &[]),
+            IpAddr::from([9, 9, 9, 9])
+        );
+        assert_eq!(
+            ip(&["1.1.1.1"], [9, 9, 9, 9], &[[127, 0, 0, 1]]),
+            IpAddr::from([9, 9, 9, 9])
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_untrusted_hop() {
+        let proxies = [[127, 0, 0, 1], [10, 0, 0, 1]];
+
+        assert_eq!(
+            ip(&["6.6.6.6, 2.2.2.2"], [127, 0, 0, 1], &proxies),
+            IpAddr::from([2, 2, 2, 2])
+        );
+        assert_eq!(
+            ip(&["6.6.6.6", "2.2.2.2, 10.0.0.1"], [127, 0, 0, 1], &proxies),
+            IpAddr::from([2, 2, 2, 2])
+        );
+        assert_eq!(
+            ip(&["junk"], [127, 0, 0, 1], &proxies),
+            IpAddr::from([127, 0, 0, 1])
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    env::Env,
+    queries::{rate::BASE, Person},
+};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::time::Duration;
+use time::{Date, OffsetDateTime};
+
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<(Person, Mailbox)>,
+}
+
+/// Mails a weekly digest and a statement of the last month, each as soon as its period starts.
+/// Does nothing unless an SMTP host is configured.
+pub fn init(env: &Env, db: sqlx::PgPool) -> anyhow::Result<()> {
+    if env.smtp_host.is_empty() {
+        return Ok(());
+    }
+
+    let mut transport = if env.smtp_starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&env.smtp_host)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&env.smtp_host)
+    }
+    .port(env.smtp_port);
+
+    if !env.smtp_username.is_empty() {
+        transport = transport.credentials(Credentials::new(
+            env.smtp_username.clone(),
+            env.smtp_password.clone(),
+        ));
+    }
+
+    let mut to = Vec::new();
+    for (who, address) in [(Person::Ale, &env.mail_ale), (Person::Lu, &env.mail_lu)] {
+        if !address.is_empty() {
+            to.push((who, address.parse()?));
+        }
+    }
+
+    let mailer = Mailer {
+        transport: transport.build(),
+        from: env.mail_from.parse()?,
+        to,
+    };
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = mailer.tick(&db, OffsetDateTime::now_utc().date()).await {
+                tracing::error!("{e:?}");
+            }
+
+            tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        }
+    });
+
+    Ok(())
+}
+
+impl Mailer {
+    /// Sends what's due for the current week and month unless it went out already, so a tick
+    /// missed at the start of a period is made up by the next one.
+    async fn tick(&self, db: &sqlx::PgPool, today: Date) -> anyhow::Result<()> {
+        let (year, week, _) = today.to_iso_week_date();
+        let week = format!("{year}-W{week:02}");
+
+        let month = today - time::Duration::days(today.day().into());
+        let period = format!("{}-{:02}", month.year(), month.month() as u8);
+
+        for (who, to) in &self.to {
+            self.once(db, "digest", *who, &week, || {
+                self.digest(db, *who, to.clone())
+            })
+            .await?;
+
+            self.once(db, "statement", *who, &period, || {
+                self.statement(db, *who, to.clone(), month)
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends at most one mail of a kind per person and period, across restarts and instances.
+    /// A failed send gives the claim back so the next tick retries it.
+    async fn once<F, Fut>(
+        &self,
+        db: &sqlx::PgPool,
+        kind: &str,
+        who: Person,
+        period: &str,
+        build: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<Option<Message>>>,
+    {
+        if !crate::queries::mail::claim(db, kind, who, period).await? {
+            return Ok(());
+        }
+
+        let sent = match build().await {
+            Ok(Some(message)) => self
+                .transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(Into::into),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = sent {
+            crate::queries::mail::unclaim(db, kind, who, period).await?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn digest(
+        &self,
+        db: &sqlx::PgPool,
+        who: Person,
+        to: Mailbox,
+    ) -> anyhow::Result<Option<Message>> {
+        let count = crate::queries::summary::resolvable_count(db, who).await?;
+
+        if count.by_you == 0 {
+            return Ok(None);
+        }
+
+        let body = format!(
+            "{} item(s) are waiting for you to confirm or refuse.\n\
+             {} item(s) of yours are still waiting on the other person.\n",
+            count.by_you, count.by_other
+        );
+
+        Ok(Some(
+            Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(format!("expensas: {} pending for you", count.by_you))
+                .body(body)?,
+        ))
+    }
+
+    async fn statement(
+        &self,
+        db: &sqlx::PgPool,
+        who: Person,
+        to: Mailbox,
+        month: Date,
+    ) -> anyhow::Result<Option<Message>> {
+        let spent = crate::queries::summary::month_spent(db, who, month).await?;
+        let owed = crate::queries::summary::total_owed(db, who).await?;
+
+        let body = format!(
+            "Statement for {:0>4}-{:0>2}\n\n\
+             You spent: {}\n\
+             We spent: {}\n\n\
+             Balance (confirmed): {}\n\
+             Balance (including pending): {}\n",
+            month.year(),
+            month.month() as u8,
+            money(spent.spent_me),
+            money(spent.spent_we),
+            money(owed.definitely),
+            money(owed.definitely + owed.maybe),
+        );
+
+        Ok(Some(
+            Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(format!(
+                    "expensas: statement for {:0>4}-{:0>2}",
+                    month.year(),
+                    month.month() as u8
+                ))
+                .body(body)?,
+        ))
+    }
+}
+
+/// Amounts are stored in cents of `BASE`. Positive balances are owed to the reader.
+fn money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!(
+        "{sign}{BASE} {}.{:02}",
+        cents.abs() / 100,
+        cents.abs() % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mailer;
+    use crate::queries::Person;
+    use lettre::{AsyncSmtpTransport, Tokio1Executor};
+    use sqlx::postgres::types::PgMoney;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+    use time::{Date, Month};
+
+    /// Accepts mail on a local port and keeps each message's data, just enough SMTP for lettre.
+    fn sink() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mails = Arc::new(Mutex::new(Vec::new()));
+        let received = mails.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+                stream.write_all(b"220 sink\r\n").unwrap();
+
+                while let Some(Ok(line)) = lines.next() {
+                    let reply: &[u8] = match line.split(' ').next().unwrap_or("") {
+                        "DATA" => {
+                            stream.write_all(b"354 go on\r\n").unwrap();
+                            let data = lines
+                                .by_ref()
+                                .map_while(Result::ok)
+                                .take_while(|l| l != ".")
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            mails.lock().unwrap().push(data);
+                            b"250 kept\r\n"
+                        }
+                        "QUIT" => {
+                            stream.write_all(b"221 bye\r\n").unwrap();
+                            break;
+                        }
+                        _ => b"250 ok\r\n",
+                    };
+                    stream.write_all(reply).unwrap();
+                }
+            }
+        });
+
+        (port, received)
+    }
+
+    #[sqlx::test]
+    async fn catches_up_on_a_missed_start_of_period(db: sqlx::PgPool) {
+        let (port, mails) = sink();
+
+        let mailer = Mailer {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+                .port(port)
+                .build(),
+            from: "expensas@localhost".parse().unwrap(),
+            to: vec![(Person::Ale, "ale@localhost".parse().unwrap())],
+        };
+
+        sqlx::query(
+            "
+            INSERT INTO transfers (sender, receiver, amount, date, created_at)
+            VALUES ('Lu', 'Ale', $1, '2026-09-30', NOW())
+            ",
+        )
+        .bind(PgMoney(1234))
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let day = |d| Date::from_calendar_date(2026, Month::October, d).unwrap();
+
+        // Neither a Monday nor a 1st: both were missed.
+        mailer.tick(&db, day(8)).await.unwrap();
+        mailer.tick(&db, day(9)).await.unwrap();
+
+        let mails = mails.lock().unwrap();
+        assert_eq!(mails.len(), 2);
+        assert!(mails[0].contains("Subject: expensas: 1 pending for you"));
+        assert!(mails[1].contains("Subject: expensas: statement for 2026-09"));
+        assert!(mails[1].contains("Balance (including pending): -BRL 12.34"));
+    }
+}
@@ -1,7 +1,17 @@
+mod attachment;
 mod auth;
 mod env;
+mod exchange;
+mod grace;
+mod limit;
+mod mailer;
+mod nfce;
+mod notify;
+mod pix;
+mod push;
 mod queries;
 mod routes;
+mod webhook;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> anyhow::Result<()> {
@@ -9,7 +19,13 @@ async fn main() -> anyhow::Result<()> {
 
     let env = env::init().await?;
     let db = queries::init(&env).await?;
+    let notifications = notify::init(&db).await?;
+    webhook::init(db.clone(), notifications.subscribe())?;
+    let push_key = push::init(&env, db.clone(), notifications.subscribe())?;
+    mailer::init(&env, db.clone())?;
+    let grace = grace::init(&env, db.clone())?;
+    let attachments = attachment::init(&env).await?;
 
-    routes::init(db, env).await?;
+    routes::init(db, notifications, push_key, grace, attachments, env).await?;
     Ok(())
 }
@@ -0,0 +1,185 @@
+use roxmltree::{Document, Node};
+use time::format_description::well_known::Iso8601;
+
+/// The parts of an NFC-e (model 65 nota fiscal) worth keeping around.
+pub struct Nfce {
+    pub key: String,
+    pub emitter: String,
+    pub date: time::Date,
+    pub total: i64,
+    pub items: Vec<Item>,
+}
+
+pub struct Item {
+    pub n: i32,
+    pub description: String,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: i64,
+    pub total: i64,
+}
+
+/// Accepts both the bare `NFe` and the authorized `nfeProc` wrapping it.
+/// Item totals are net of their discounts; the trade name wins over the legal one.
+pub fn parse(xml: &str) -> Option<Nfce> {
+    let doc = Document::parse(xml).ok()?;
+    let inf = find(doc.root(), "infNFe")?;
+
+    let key = inf.attribute("Id")?.trim_start_matches("NFe").to_owned();
+
+    let emit = find(inf, "emit")?;
+    let emitter = text(emit, "xFant").or_else(|| text(emit, "xNome"))?;
+
+    let ide = find(inf, "ide")?;
+    let issued = text(ide, "dhEmi").or_else(|| text(ide, "dEmi"))?;
+    let date = time::Date::parse(issued.get(..10)?, &Iso8601::DEFAULT).ok()?;
+
+    let total = cents(&text(find(inf, "ICMSTot")?, "vNF")?)?;
+
+    let items = inf
+        .children()
+        .filter(|n| n.tag_name().name() == "det")
+        .map(|det| {
+            let prod = find(det, "prod")?;
+            let discount = text(prod, "vDesc").map_or(Some(0), |d| cents(&d))?;
+
+            Some(Item {
+                n: det.attribute("nItem")?.parse().ok()?,
+                description: text(prod, "xProd")?,
+                quantity: text(prod, "qCom")?.parse().ok()?,
+                unit: text(prod, "uCom")?,
+                unit_price: cents(&text(prod, "vUnCom")?)?,
+                total: cents(&text(prod, "vProd")?)? - discount,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Nfce {
+        key,
+        emitter,
+        date,
+        total,
+        items,
+    })
+}
+
+fn find<'a, 'i>(node: Node<'a, 'i>, name: &str) -> Option<Node<'a, 'i>> {
+    node.descendants().find(|n| n.tag_name().name() == name)
+}
+
+fn text(node: Node, name: &str) -> Option<String> {
+    let text = find(node, name)?.text()?.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// Unit prices may carry up to ten decimal places; they're rounded to the cent,
+/// half away from zero. Either side of the point may be left out, but not both,
+/// and nothing beyond what fits in an `i64` of cents is read.
+fn cents(value: &str) -> Option<i64> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value),
+    };
+
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+
+    if int.is_empty() && frac.is_empty()
+        || !(int.chars().chain(frac.chars())).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let int = match int {
+        "" => 0,
+        int => int.parse::<i64>().ok()?,
+    };
+    let frac = frac
+        .chars()
+        .chain("000".chars())
+        .take(3)
+        .collect::<String>()
+        .parse::<i64>()
+        .ok()?;
+
+    int.checked_mul(100)?
+        .checked_add((frac + 5) / 10)
+        .map(|cents| sign * cents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month};
+
+    #[test]
+    fn reads_an_authorized_document() {
+        let nfce = parse(include_str!("../fixtures/nfce-proc.xml")).unwrap();
+
+        assert_eq!(nfce.key, "35261012345678000195650010000012341000012345");
+        assert_eq!(nfce.emitter, "Mercado Central");
+        assert_eq!(
+            nfce.date,
+            Date::from_calendar_date(2026, Month::October, 10).unwrap()
+        );
+        assert_eq!(nfce.total, 2554);
+
+        let items = nfce
+            .items
+            .iter()
+            .map(|i| {
+                (
+                    i.n,
+                    i.description.as_str(),
+                    i.quantity,
+                    i.unit_price,
+                    i.total,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            items,
+            [
+                (1, "ARROZ TIPO 1 5KG", 1.0, 2299, 2299),
+                (2, "BANANA PRATA KG", 0.5, 598, 250),
+                (3, "SACOLA", 1.0, 5, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_a_bare_document_with_its_legal_name() {
+        let nfce = parse(include_str!("../fixtures/nfce-bare.xml")).unwrap();
+
+        assert_eq!(nfce.emitter, "PADARIA DO BAIRRO LTDA");
+        assert_eq!(
+            nfce.date,
+            Date::from_calendar_date(2026, Month::September, 30).unwrap()
+        );
+        assert_eq!(nfce.total, 1050);
+        assert_eq!(nfce.items.len(), 1);
+        assert_eq!(nfce.items[0].unit, "UN");
+    }
+
+    #[test]
+    fn rounds_to_the_cent() {
+        assert_eq!(cents("12.34"), Some(1234));
+        assert_eq!(cents("4.9950000000"), Some(500));
+        assert_eq!(cents(".50"), Some(50));
+        assert_eq!(cents("7"), Some(700));
+        assert_eq!(cents("7."), Some(700));
+        assert_eq!(cents("-1.005"), Some(-101));
+        assert_eq!(cents("-.5"), Some(-50));
+        assert_eq!(cents("."), None);
+        assert_eq!(cents("1,50"), None);
+        assert_eq!(cents("--1"), None);
+        assert_eq!(cents("92233720368547758.07"), Some(i64::MAX));
+        assert_eq!(cents("92233720368547758.08"), None);
+        assert_eq!(cents("100000000000000000"), None);
+    }
+
+    #[test]
+    fn refuses_a_total_too_large() {
+        assert!(parse(include_str!("../fixtures/nfce-overflow.xml")).is_none());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::queries::{event::Entity, Person};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// An audit event as announced by the `events_notify` trigger, minus the request metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i32,
+    pub actor: Option<Person>,
+    pub session_id: Option<i32>,
+    pub entity: Entity,
+    pub entity_id: i32,
+    pub old_state: Option<String>,
+    pub new_state: String,
+    /// Set when the event is about a personal expense, which only its creator may hear of.
+    #[serde(default, skip_serializing)]
+    pub private_to: Option<Person>,
+}
+
+/// Relays every `events` notification to in-process subscribers. Since they come from
+/// Postgres, subscribers see transitions made through any backend instance.
+pub async fn init(db: &sqlx::PgPool) -> anyhow::Result<broadcast::Sender<Notification>> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen("events").await?;
+
+    let (tx, _) = broadcast::channel(64);
+    let sender = tx.clone();
+
+    tokio::spawn(async move {
+        loop {
+            match listener.recv().await {
+                Ok(n) => match serde_json::from_str::<Notification>(n.payload()) {
+                    Ok(n) => {
+                        let _ = sender.send(n);
+                    }
+                    Err(e) => tracing::error!("{e:?}"),
+                },
+                Err(e) => {
+                    tracing::error!("{e:?}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+
+    Ok(tx)
+}
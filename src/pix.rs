@@ -0,0 +1,75 @@
+use crate::queries::pix::PixKey;
+
+/// Builds a static Pix BR Code: an EMV QRCPS payload of ID-length-value fields,
+/// closed by a CRC16/CCITT-FALSE over everything before the checksum value.
+/// There's none for amounts that aren't positive or don't fit the 13 characters of field 54.
+pub fn payload(key: &PixKey, cents: i64, txid: &str) -> Option<String> {
+    let account = field("00", "br.gov.bcb.pix") + &field("01", &key.key);
+    let amount = format!("{}.{:02}", cents / 100, cents % 100);
+
+    if cents <= 0 || amount.len() > 13 {
+        return None;
+    }
+
+    let mut payload = field("00", "01")
+        + &field("01", "11")
+        + &field("26", &account)
+        + &field("52", "0000")
+        + &field("53", "986")
+        + &field("54", &amount)
+        + &field("58", "BR")
+        + &field("59", &key.name)
+        + &field("60", &key.city)
+        + &field("62", &field("05", txid))
+        + "6304";
+
+    let crc = crc16(payload.as_bytes());
+    payload.push_str(&format!("{crc:04X}"));
+    Some(payload)
+}
+
+fn field(id: &str, value: &str) -> String {
+    format!("{id}{:02}{value}", value.len())
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::payload;
+    use crate::queries::pix::PixKey;
+
+    fn key() -> PixKey {
+        PixKey {
+            key: String::from("ale@example.com"),
+            name: String::from("Ale"),
+            city: String::from("Sao Paulo"),
+        }
+    }
+
+    #[test]
+    fn formats_the_amount() {
+        let p = payload(&key(), 1050, "***").unwrap();
+        assert!(p.contains("540510.50"));
+
+        let p = payload(&key(), 999_999_999_999, "***").unwrap();
+        assert!(p.contains("54139999999999.99"));
+    }
+
+    #[test]
+    fn rejects_amounts_field_54_cant_carry() {
+        assert_eq!(payload(&key(), 0, "***"), None);
+        assert_eq!(payload(&key(), -50, "***"), None);
+        assert_eq!(payload(&key(), 1_000_000_000_000, "***"), None);
+    }
+}
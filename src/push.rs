@@ -0,0 +1,249 @@
+use crate::{
+    env::Env,
+    notify::Notification,
+    queries::{push::Subscription, Person},
+};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use web_push::{
+    request_builder::build_request, ContentEncoding, PartialVapidSignatureBuilder,
+    SubscriptionInfo, VapidSignatureBuilder, WebPushMessageBuilder, URL_SAFE_NO_PAD,
+};
+
+/// Starts pushing to the counterparty whenever something starts waiting on them.
+/// Returns the VAPID public key for subscribing, or nothing when push isn't configured.
+pub fn init(
+    env: &Env,
+    db: sqlx::PgPool,
+    mut rx: broadcast::Receiver<Notification>,
+) -> anyhow::Result<Option<String>> {
+    if env.vapid_private_key.is_empty() {
+        return Ok(None);
+    }
+
+    let vapid = VapidSignatureBuilder::from_base64_no_sub(&env.vapid_private_key, URL_SAFE_NO_PAD)?;
+    let public_key = base64::encode_config(vapid.get_public_key(), URL_SAFE_NO_PAD);
+    let subject = env.vapid_subject.clone();
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    tokio::spawn(async move {
+        loop {
+            let n = match rx.recv().await {
+                Ok(n) => n,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+
+            if let Err(e) = notify(&db, &client, &vapid, &subject, &n).await {
+                tracing::error!("{e:?}");
+            }
+        }
+    });
+
+    Ok(Some(public_key))
+}
+
+async fn notify(
+    db: &sqlx::PgPool,
+    client: &reqwest::Client,
+    vapid: &PartialVapidSignatureBuilder,
+    subject: &str,
+    n: &Notification,
+) -> anyhow::Result<()> {
+    let target = match (n.actor, n.new_state.as_str()) {
+        (Some(Person::Ale), "Pending" | "Confirmable") => Person::Lu,
+        (Some(Person::Lu), "Pending" | "Confirmable") => Person::Ale,
+        _ => return Ok(()),
+    };
+
+    // The claim is held in a transaction until some push gets through, so it isn't
+    // left behind when every attempt fails and another instance can still take over.
+    let mut transaction = db.begin().await?;
+    if !crate::queries::push::claim(&mut transaction, n.id).await? {
+        return Ok(());
+    }
+
+    let payload = serde_json::to_vec(n)?;
+    let subscriptions = crate::queries::push::subscriptions(db, target).await?;
+    let mut done = subscriptions.is_empty();
+
+    for s in subscriptions {
+        match send(client, vapid, subject, &s, &payload).await {
+            Ok(reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE) => {
+                crate::queries::push::expire(db, &s.endpoint).await?;
+                done = true;
+            }
+            Ok(status) if !status.is_success() => {
+                tracing::warn!("Push to {} answered {status}", s.endpoint)
+            }
+            Ok(_) => done = true,
+            Err(e) => tracing::warn!("Push to {} failed: {e:?}", s.endpoint),
+        }
+    }
+
+    if done {
+        transaction.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Encrypts the payload as `aes128gcm` (RFC 8291) and signs the request with VAPID (RFC 8292).
+async fn send(
+    client: &reqwest::Client,
+    vapid: &PartialVapidSignatureBuilder,
+    subject: &str,
+    s: &Subscription,
+    payload: &[u8],
+) -> anyhow::Result<reqwest::StatusCode> {
+    let info = SubscriptionInfo::new(&s.endpoint, &s.p256dh, &s.auth);
+
+    let mut signature = vapid.clone().add_sub_info(&info);
+    if !subject.is_empty() {
+        signature.add_claim("sub", subject);
+    }
+
+    let mut message = WebPushMessageBuilder::new(&info);
+    message.set_ttl(60 * 60 * 24);
+    message.set_payload(ContentEncoding::Aes128Gcm, payload);
+    message.set_vapid_signature(signature.build()?);
+
+    let request = reqwest::Request::try_from(build_request::<Vec<u8>>(message.build()?))?;
+    Ok(client.execute(request).await?.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{notify::Notification, queries::event::Entity, queries::Person};
+    use axum::{body::Bytes, extract::State, http::StatusCode, routing::post, Router};
+    use openssl::{
+        bn::BigNumContext,
+        ec::{EcGroup, EcKey, PointConversionForm},
+        nid::Nid,
+    };
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+    use web_push::{VapidSignatureBuilder, URL_SAFE_NO_PAD};
+
+    fn b64(bytes: &[u8]) -> String {
+        base64::encode_config(bytes, URL_SAFE_NO_PAD)
+    }
+
+    type Stub = (Arc<Mutex<StatusCode>>, Arc<Mutex<Vec<Vec<u8>>>>);
+
+    /// A push service on a local port, keeping each body and answering with whatever status is set.
+    async fn stub(stub: Stub) -> String {
+        let app = Router::new()
+            .route(
+                "/push",
+                post(
+                    |State((status, bodies)): State<Stub>, body: Bytes| async move {
+                        bodies.lock().unwrap().push(body.to_vec());
+                        *status.lock().unwrap()
+                    },
+                ),
+            )
+            .with_state(stub);
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let url = format!("http://{}/push", server.local_addr());
+        tokio::spawn(server);
+
+        url
+    }
+
+    #[sqlx::test]
+    async fn keeps_the_claim_only_once_pushed(db: sqlx::PgPool) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let vapid = EcKey::generate(&group).unwrap();
+        let vapid = VapidSignatureBuilder::from_base64_no_sub(
+            &b64(&vapid.private_key().to_vec_padded(32).unwrap()),
+            URL_SAFE_NO_PAD,
+        )
+        .unwrap();
+
+        let browser = EcKey::generate(&group).unwrap();
+        let p256dh = browser
+            .public_key()
+            .to_bytes(
+                &group,
+                PointConversionForm::UNCOMPRESSED,
+                &mut BigNumContext::new().unwrap(),
+            )
+            .unwrap();
+
+        let status = Arc::new(Mutex::new(StatusCode::INTERNAL_SERVER_ERROR));
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let endpoint = stub((status.clone(), bodies.clone())).await;
+
+        crate::queries::session::ask(&db, Person::Ale)
+            .await
+            .unwrap();
+        let lu = crate::queries::session::ask(&db, Person::Lu).await.unwrap();
+        crate::queries::push::subscribe(&db, lu, &endpoint, &b64(&p256dh), &b64(&[7; 16]))
+            .await
+            .unwrap();
+
+        crate::queries::event::record(
+            &db,
+            Some(Person::Ale),
+            None,
+            Entity::Expense,
+            1,
+            None,
+            "Pending",
+            &serde_json::Value::Null,
+        )
+        .await
+        .unwrap();
+
+        let n = Notification {
+            id: sqlx::query_scalar("SELECT MAX(id) FROM events")
+                .fetch_one(&db)
+                .await
+                .unwrap(),
+            actor: Some(Person::Ale),
+            session_id: None,
+            entity: Entity::Expense,
+            entity_id: 1,
+            old_state: None,
+            new_state: String::from("Pending"),
+            private_to: None,
+        };
+
+        let claimed = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM push_sent")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+        };
+
+        let client = reqwest::Client::new();
+        super::notify(&db, &client, &vapid, "", &n).await.unwrap();
+        assert_eq!(claimed().await, 0);
+
+        *status.lock().unwrap() = StatusCode::CREATED;
+        super::notify(&db, &client, &vapid, "", &n).await.unwrap();
+        assert_eq!(claimed().await, 1);
+
+        // Only the browser holding the subscription's keys can read what was pushed.
+        let keys =
+            ece::EcKeyComponents::new(browser.private_key().to_vec_padded(32).unwrap(), p256dh);
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        for body in bodies.iter() {
+            let payload = ece::decrypt(&keys, &[7; 16], body).unwrap();
+            let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+            assert_eq!(payload["id"], n.id);
+            assert_eq!(payload["actor"], "Ale");
+            assert_eq!(payload["new_state"], "Pending");
+            assert!(payload.get("private_to").is_none());
+        }
+    }
+}
@@ -1,7 +1,18 @@
+pub mod attachment;
+pub mod event;
 pub mod expense;
+pub mod income;
+pub mod mail;
+pub mod passkey;
+pub mod pix;
+pub mod push;
+pub mod rate;
+pub mod receipt;
 pub mod session;
+pub mod stats;
 pub mod summary;
 pub mod transfer;
+pub mod webhook;
 
 use serde::{Deserialize, Serialize};
 use sqlx::Type;
@@ -22,13 +33,48 @@ pub enum Person {
     Lu,
 }
 
+impl Person {
+    /// Whoever is on the other side of an expense or transfer. Everything assumes two people,
+    /// which this match stops compiling as soon as that changes.
+    pub fn other(self) -> Person {
+        match self {
+            Person::Ale => Person::Lu,
+            Person::Lu => Person::Ale,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Type, Serialize, Deserialize)]
 #[sqlx(type_name = "split")]
 pub enum Split {
     Proportional2to1,
     Proportional3to2,
+    ProportionalIncome,
     Arbitrary,
     Evenly,
+    Itemized,
+    Personal,
+}
+
+impl Split {
+    /// What the other person owes of `paid`, or nothing when the split doesn't allow it.
+    /// Income splits depend on a ratio and itemized ones on their items, so neither is here.
+    pub fn owed(self, payer: Person, paid: i64, owed: Option<i64>) -> Option<i64> {
+        match (self, owed) {
+            (Split::Arbitrary, Some(owed)) if owed <= paid => Some(owed),
+            (Split::Proportional2to1, None) => match payer {
+                Person::Ale => Some(paid / 3),
+                Person::Lu => Some(paid * 2 / 3),
+            },
+            (Split::Proportional3to2, None) => match payer {
+                Person::Ale => Some(paid * 2 / 5),
+                Person::Lu => Some(paid * 3 / 5),
+            },
+            (Split::Evenly, None) => Some(paid / 2),
+            (Split::Personal, None) => Some(0),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Type, Serialize, Deserialize)]
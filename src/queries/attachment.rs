@@ -0,0 +1,233 @@
+use super::{event::Entity, Person};
+use sqlx::{Executor, Postgres};
+
+pub struct Attachment {
+    pub id: i32,
+    pub entity: Entity,
+    pub entity_id: i32,
+    pub creator: Person,
+    pub name: String,
+    pub mime: String,
+    pub size: i32,
+    pub sha256: String,
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Only expenses and transfers that exist take attachments,
+/// and personal expenses only from their creator.
+pub async fn attachable(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    entity: Entity,
+    entity_id: i32,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT CASE $1::entity
+            WHEN 'Expense' THEN EXISTS (
+                SELECT FROM expenses
+                WHERE id = $2 AND (split <> 'Personal' OR creator = $3)
+            )
+            WHEN 'Transfer' THEN EXISTS (SELECT FROM transfers WHERE id = $2)
+            ELSE FALSE
+        END as "attachable!"
+        "#,
+        entity as Entity,
+        entity_id,
+        viewer as Person
+    )
+    .fetch_one(db)
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    db: impl Executor<'_, Database = Postgres>,
+    entity: Entity,
+    entity_id: i32,
+    creator: Person,
+    name: &str,
+    mime: &str,
+    size: i32,
+    sha256: &str,
+) -> sqlx::Result<i32> {
+    sqlx::query_scalar!(
+        "
+        INSERT INTO attachments (entity, entity_id, creator, name, mime, size, sha256, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+        RETURNING id
+        ",
+        entity as Entity,
+        entity_id,
+        creator as Person,
+        name,
+        mime,
+        size,
+        sha256
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn get(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    id: i32,
+) -> sqlx::Result<Option<Attachment>> {
+    sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT
+            id,
+            entity as "entity: Entity",
+            entity_id,
+            creator as "creator: Person",
+            name,
+            mime,
+            size,
+            sha256,
+            created_at
+        FROM attachments a
+        WHERE id = $1
+            AND NOT EXISTS (
+                SELECT FROM expenses e
+                WHERE a.entity = 'Expense'
+                    AND e.id = a.entity_id
+                    AND e.split = 'Personal'
+                    AND e.creator <> $2
+            )
+        "#,
+        id,
+        viewer as Person
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn of(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    entity: Entity,
+    entity_id: i32,
+) -> sqlx::Result<Vec<Attachment>> {
+    sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT
+            id,
+            entity as "entity: Entity",
+            entity_id,
+            creator as "creator: Person",
+            name,
+            mime,
+            size,
+            sha256,
+            created_at
+        FROM attachments a
+        WHERE entity = $1
+            AND entity_id = $2
+            AND NOT EXISTS (
+                SELECT FROM expenses e
+                WHERE a.entity = 'Expense'
+                    AND e.id = a.entity_id
+                    AND e.split = 'Personal'
+                    AND e.creator <> $3
+            )
+        ORDER BY id
+        "#,
+        entity as Entity,
+        entity_id,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn all(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+) -> sqlx::Result<Vec<Attachment>> {
+    sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT
+            id,
+            entity as "entity: Entity",
+            entity_id,
+            creator as "creator: Person",
+            name,
+            mime,
+            size,
+            sha256,
+            created_at
+        FROM attachments a
+        WHERE NOT EXISTS (
+            SELECT FROM expenses e
+            WHERE a.entity = 'Expense'
+                AND e.id = a.entity_id
+                AND e.split = 'Personal'
+                AND e.creator <> $1
+        )
+        ORDER BY id
+        "#,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Counts one more attachment of the blob, holding its row until the transaction ends.
+pub async fn reference(
+    db: impl Executor<'_, Database = Postgres>,
+    sha256: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO attachment_blobs (sha256, refs)
+        VALUES ($1, 1)
+        ON CONFLICT (sha256) DO UPDATE SET refs = attachment_blobs.refs + 1
+        ",
+        sha256
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Returns the hash of the blob the attachment pointed at.
+pub async fn delete(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    by: Person,
+) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar!(
+        "
+        DELETE FROM attachments
+        WHERE id = $1 AND creator = $2
+        RETURNING sha256
+        ",
+        id,
+        by as Person
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Counts one attachment of the blob less, telling whether that was the last one.
+/// The row stays at zero and is held until the transaction ends, like `reference` holds it.
+pub async fn dereference(
+    db: impl Executor<'_, Database = Postgres>,
+    sha256: &str,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE attachment_blobs
+        SET refs = refs - 1
+        WHERE sha256 = $1
+        RETURNING refs = 0 as "orphan!"
+        "#,
+        sha256
+    )
+    .fetch_one(db)
+    .await
+}
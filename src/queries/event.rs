@@ -0,0 +1,93 @@
+use super::Person;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, Executor, Postgres, Type};
+
+#[derive(Debug, Clone, Copy, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "entity")]
+pub enum Entity {
+    Expense,
+    Transfer,
+    Session,
+    Passkey,
+}
+
+pub struct Event {
+    pub id: i32,
+    pub actor: Option<Person>,
+    pub session_id: Option<i32>,
+    pub entity: Entity,
+    pub entity_id: i32,
+    pub old_state: Option<String>,
+    pub new_state: String,
+    pub meta: serde_json::Value,
+    pub created_at: time::OffsetDateTime,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn record(
+    db: impl Executor<'_, Database = Postgres>,
+    actor: Option<Person>,
+    session_id: Option<i32>,
+    entity: Entity,
+    entity_id: i32,
+    old_state: Option<&str>,
+    new_state: &str,
+    meta: &(impl Serialize + Sync),
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO events (actor, session_id, entity, entity_id, old_state, new_state, meta, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+        ",
+        actor as Option<Person>,
+        session_id,
+        entity as Entity,
+        entity_id,
+        old_state,
+        new_state,
+        Json(meta) as _
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Leaves out what happened to someone else's personal expenses.
+pub async fn page(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    before: Option<i32>,
+    limit: i64,
+) -> sqlx::Result<Vec<Event>> {
+    sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+            id,
+            actor as "actor: Person",
+            session_id,
+            entity as "entity: Entity",
+            entity_id,
+            old_state,
+            new_state,
+            meta,
+            created_at
+        FROM events v
+        WHERE ($1::INTEGER IS NULL OR id < $1)
+            AND NOT EXISTS (
+                SELECT FROM expenses e
+                WHERE v.entity = 'Expense'
+                    AND e.id = v.entity_id
+                    AND e.split = 'Personal'
+                    AND e.creator <> $3
+            )
+        ORDER BY id DESC
+        LIMIT $2
+        "#,
+        before,
+        limit,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
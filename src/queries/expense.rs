@@ -1,4 +1,5 @@
 use super::{Label, Person, Split};
+use crate::exchange::Rate;
 use sqlx::{postgres::types::PgMoney, Executor, Postgres};
 use time::Date;
 
@@ -15,6 +16,12 @@ pub struct Expense {
     pub confirmed_at: Option<time::OffsetDateTime>,
     pub refused_at: Option<time::OffsetDateTime>,
     pub created_at: time::OffsetDateTime,
+    pub unusual: bool,
+    pub ratio: Option<f64>,
+    pub currency: String,
+    pub original_paid: Option<i64>,
+    pub original_owed: Option<i64>,
+    pub exchange_rate: Option<f64>,
 }
 
 pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec<Expense>> {
@@ -33,7 +40,13 @@ pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec
             owed,
             confirmed_at,
             refused_at,
-            created_at
+            created_at,
+            unusual,
+            ratio::FLOAT8 as ratio,
+            currency,
+            original_paid,
+            original_owed,
+            exchange_rate::FLOAT8 as exchange_rate
         FROM expenses
         "#
     )
@@ -61,6 +74,8 @@ pub async fn resolvable(
     .await
 }
 
+/// Foreign expenses carry their `original_*` amounts in `currency`,
+/// with `paid` and `owed` converted at `exchange_rate`.
 #[allow(clippy::too_many_arguments)]
 pub async fn submit(
     db: impl Executor<'_, Database = Postgres>,
@@ -72,11 +87,23 @@ pub async fn submit(
     date: Date,
     paid: i64,
     owed: i64,
+    ratio: Option<f64>,
+    unusual: bool,
+    currency: &str,
+    original_paid: Option<i64>,
+    original_owed: Option<i64>,
+    exchange_rate: Option<Rate>,
 ) -> sqlx::Result<i32> {
     sqlx::query_scalar!(
         "
-        INSERT INTO expenses (creator, payer, split, label, detail, date, paid, owed, created_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+        INSERT INTO expenses (
+            creator, payer, split, label, detail, date, paid, owed, ratio, unusual,
+            currency, original_paid, original_owed, exchange_rate, confirmed_at, created_at
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9::FLOAT8, $10,
+            $11, $12, $13, $14::TEXT::NUMERIC, CASE WHEN $3::split = 'Personal' THEN NOW() END, NOW()
+        )
         RETURNING id
         ",
         creator as Person,
@@ -86,12 +113,151 @@ pub async fn submit(
         detail,
         date,
         PgMoney(paid),
-        PgMoney(owed)
+        PgMoney(owed),
+        ratio,
+        unusual,
+        currency,
+        original_paid,
+        original_owed,
+        exchange_rate.map(|r| r.to_string())
     )
     .fetch_one(db)
     .await
 }
 
+pub struct Foreign {
+    pub payer: Person,
+    pub split: Split,
+    pub date: Date,
+    pub ratio: Option<f64>,
+    pub currency: String,
+    pub original_paid: i64,
+    pub original_owed: i64,
+}
+
+pub async fn foreign(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+) -> sqlx::Result<Option<Foreign>> {
+    sqlx::query_as!(
+        Foreign,
+        r#"
+        SELECT
+            payer as "payer: Person",
+            split as "split: Split",
+            date,
+            ratio::FLOAT8 as ratio,
+            currency,
+            original_paid as "original_paid!",
+            original_owed as "original_owed!"
+        FROM expenses
+        WHERE id = $1 AND currency <> 'BRL'
+        "#,
+        id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn convert(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    paid: i64,
+    owed: i64,
+    exchange_rate: Rate,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        UPDATE expenses
+        SET paid = $2, owed = $3, exchange_rate = $4::TEXT::NUMERIC
+        WHERE id = $1
+        ",
+        id,
+        PgMoney(paid),
+        PgMoney(owed),
+        exchange_rate.to_string()
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub struct Item {
+    pub n: i32,
+    pub payer: Person,
+    pub label: Option<Label>,
+    pub detail: Option<String>,
+    pub amount: PgMoney,
+    pub split: Option<Split>,
+    pub only: Option<Person>,
+    pub owed: PgMoney,
+}
+
+/// Items are only checked against their expense when the transaction commits.
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_item(
+    db: impl Executor<'_, Database = Postgres>,
+    expense_id: i32,
+    n: i32,
+    payer: Person,
+    label: Option<Label>,
+    detail: Option<&str>,
+    amount: i64,
+    split: Option<Split>,
+    only: Option<Person>,
+    owed: i64,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO expense_items (expense_id, n, payer, label, detail, amount, split, only_for, owed)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ",
+        expense_id,
+        n,
+        payer as Person,
+        label as Option<Label>,
+        detail,
+        PgMoney(amount),
+        split as Option<Split>,
+        only as Option<Person>,
+        PgMoney(owed)
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Items of the other person's personal expense aren't there for `viewer`.
+pub async fn items(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    expense_id: i32,
+) -> sqlx::Result<Vec<Item>> {
+    sqlx::query_as!(
+        Item,
+        r#"
+        SELECT
+            i.n,
+            i.payer as "payer: Person",
+            i.label as "label: Label",
+            i.detail,
+            i.amount,
+            i.split as "split: Split",
+            i.only_for as "only: Person",
+            i.owed
+        FROM expense_items i
+        JOIN expenses e ON e.id = i.expense_id
+        WHERE i.expense_id = $1
+            AND (e.split <> 'Personal' OR e.creator = $2)
+        ORDER BY i.n
+        "#,
+        expense_id,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
 pub async fn confirm(
     db: impl Executor<'_, Database = Postgres>,
     id: i32,
@@ -140,6 +306,7 @@ pub async fn refuse(
 
 pub async fn splitrecc(
     db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
     payer: Person,
     label: Label,
 ) -> sqlx::Result<Option<Split>> {
@@ -149,13 +316,230 @@ pub async fn splitrecc(
         FROM expenses
         WHERE confirmed_at IS NOT NULL
             AND payer = $1 AND label = $2
+            AND (split <> 'Personal' OR creator = $3)
         GROUP BY split
         ORDER BY COUNT(1) DESC
         LIMIT 1
         "#,
         payer as Person,
-        label as Label
+        label as Label,
+        viewer as Person
     )
     .fetch_optional(db)
     .await
 }
+
+pub struct Vote {
+    pub label: Label,
+    pub split: Split,
+    pub weight: f64,
+    pub weight_payer: f64,
+    pub owed_ratio: Option<f64>,
+}
+
+/// Weighs confirmed expenses by how many of the draft's words their detail shares,
+/// discounted by how far apart the amounts are, and adds it all up per label and split.
+/// Only the payer's own expenses count towards `weight_payer` and `owed_ratio`,
+/// and only `viewer`'s personal expenses count at all.
+pub async fn votes(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    payer: Person,
+    detail: &str,
+    paid: i64,
+) -> sqlx::Result<Vec<Vote>> {
+    sqlx::query_as!(
+        Vote,
+        r#"
+        WITH draft AS (
+            SELECT DISTINCT t
+            FROM regexp_split_to_table(LOWER($2), '[^[:alnum:]]+') t
+            WHERE LENGTH(t) >= 2
+        ), matches AS (
+            SELECT
+                e.label,
+                e.split,
+                e.payer,
+                e.owed / NULLIF(e.paid, 0::money) as ratio,
+                COUNT(DISTINCT t)::FLOAT8 / (SELECT COUNT(1) FROM draft)
+                    / (1 + ABS(LN(GREATEST($3::BIGINT, 1)::NUMERIC / GREATEST(cents(e.paid), 1)))) as score
+            FROM expenses e
+            CROSS JOIN regexp_split_to_table(LOWER(e.detail), '[^[:alnum:]]+') t
+            WHERE e.confirmed_at IS NOT NULL
+                AND (e.split <> 'Personal' OR e.creator = $4)
+                AND t IN (SELECT t FROM draft)
+            GROUP BY e.id
+        )
+        SELECT
+            label as "label!: Label",
+            split as "split!: Split",
+            SUM(score)::FLOAT8 as "weight!",
+            COALESCE(SUM(score) FILTER (WHERE payer = $1), 0)::FLOAT8 as "weight_payer!",
+            PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY ratio) FILTER (WHERE payer = $1) as owed_ratio
+        FROM matches
+        GROUP BY label, split
+        "#,
+        payer as Person,
+        detail,
+        paid,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub struct Candidate {
+    pub id: i32,
+    pub creator: Person,
+    pub detail: Option<String>,
+    pub date: Date,
+    pub paid: i64,
+    pub confirmed: bool,
+}
+
+/// Non-refused expenses by the same payer, for the same amount in `currency`, within `days`
+/// of `date` and with a detail alike enough that both could be the same thing.
+/// Someone else's personal expenses are none of `viewer`'s business.
+#[allow(clippy::too_many_arguments)]
+pub async fn duplicates_of(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    payer: Person,
+    currency: &str,
+    paid: i64,
+    date: Date,
+    detail: Option<&str>,
+    days: i32,
+) -> sqlx::Result<Vec<Candidate>> {
+    sqlx::query_as!(
+        Candidate,
+        r#"
+        SELECT
+            id,
+            creator as "creator: Person",
+            detail,
+            date,
+            cents(paid) as "paid!",
+            confirmed_at IS NOT NULL as "confirmed!"
+        FROM expenses
+        WHERE refused_at IS NULL
+            AND payer = $1
+            AND currency = $7
+            AND COALESCE(original_paid, cents(paid)) = $2::BIGINT
+            AND ABS(date - $3) <= $5
+            AND details_alike(detail, $4)
+            AND (split <> 'Personal' OR creator = $6)
+        ORDER BY date, id
+        "#,
+        payer as Person,
+        paid,
+        date,
+        detail,
+        days,
+        viewer as Person,
+        currency
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Every pair `duplicates_of` would have caught, across the whole history.
+pub async fn duplicates(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    days: i32,
+) -> sqlx::Result<Vec<(i32, i32)>> {
+    sqlx::query!(
+        "
+        SELECT a.id as first, b.id as second
+        FROM expenses a
+        JOIN expenses b
+            ON a.id < b.id
+            AND a.payer = b.payer
+            AND a.currency = b.currency
+            AND COALESCE(a.original_paid, cents(a.paid)) = COALESCE(b.original_paid, cents(b.paid))
+            AND ABS(a.date - b.date) <= $1
+            AND details_alike(a.detail, b.detail)
+        WHERE a.refused_at IS NULL
+            AND b.refused_at IS NULL
+            AND (a.split <> 'Personal' OR a.creator = $2)
+            AND (b.split <> 'Personal' OR b.creator = $2)
+        ORDER BY a.id, b.id
+        ",
+        days,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+    .map(|rs| rs.into_iter().map(|r| (r.first, r.second)).collect())
+}
+
+/// Tukey's fences over the confirmed amounts paid by `payer` under `label`.
+pub struct Fences {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Fences {
+    pub fn contain(&self, paid: i64) -> bool {
+        (self.low..=self.high).contains(&(paid as f64))
+    }
+}
+
+/// Nothing is unusual until there are at least five expenses to compare against.
+/// The spread counts as at least a quarter of the median, and at least a unit, so a bill
+/// that's always the same doesn't make every cent of difference unusual.
+pub async fn fences(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    payer: Person,
+    label: Label,
+) -> sqlx::Result<Option<Fences>> {
+    sqlx::query_as!(
+        Fences,
+        r#"
+        SELECT
+            q1 - 1.5 * GREATEST(q3 - q1, q2 / 4, 100) as "low!",
+            q3 + 1.5 * GREATEST(q3 - q1, q2 / 4, 100) as "high!"
+        FROM (
+            SELECT
+                PERCENTILE_CONT(0.25) WITHIN GROUP (ORDER BY cents(paid)) as q1,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY cents(paid)) as q2,
+                PERCENTILE_CONT(0.75) WITHIN GROUP (ORDER BY cents(paid)) as q3
+            FROM expenses
+            WHERE confirmed_at IS NOT NULL
+                AND payer = $1 AND label = $2
+                AND (split <> 'Personal' OR creator = $3)
+            HAVING COUNT(1) >= 5
+        ) _
+        "#,
+        payer as Person,
+        label as Label,
+        viewer as Person
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Resolves, on nobody's behalf, everything left pending for longer than `days`.
+pub async fn expire(
+    db: impl Executor<'_, Database = Postgres>,
+    days: i32,
+    confirm: bool,
+) -> sqlx::Result<Vec<i32>> {
+    sqlx::query_scalar!(
+        "
+        UPDATE expenses
+        SET confirmed_at = CASE WHEN $2 THEN NOW() END,
+            refused_at = CASE WHEN $2 THEN NULL ELSE NOW() END
+        WHERE confirmed_at IS NULL
+            AND refused_at IS NULL
+            AND created_at < NOW() - make_interval(days => $1)
+        RETURNING id
+        ",
+        days,
+        confirm
+    )
+    .fetch_all(db)
+    .await
+}
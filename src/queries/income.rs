@@ -0,0 +1,79 @@
+use super::Person;
+use sqlx::{postgres::types::PgMoney, Executor, Postgres};
+
+pub struct Income {
+    pub who: Person,
+    pub month: time::Date,
+    pub amount: PgMoney,
+}
+
+pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec<Income>> {
+    sqlx::query_as!(
+        Income,
+        r#"
+        SELECT who as "who: Person", month, amount
+        FROM incomes
+        ORDER BY month DESC, who
+        "#
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn set(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+    month: time::Date,
+    amount: i64,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO incomes (who, month, amount, updated_at)
+        VALUES ($1, date_trunc('month', $2::DATE), $3, NOW())
+        ON CONFLICT (who, month) DO UPDATE
+        SET amount = EXCLUDED.amount, updated_at = NOW()
+        ",
+        who as Person,
+        month,
+        PgMoney(amount)
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// The share of an expense owed by whoever didn't pay, by the latest income each person
+/// recorded up to the month of `date`, rounded the way `expenses.ratio` stores it.
+/// Nothing when either is missing.
+pub async fn ratio(
+    db: impl Executor<'_, Database = Postgres>,
+    payer: Person,
+    date: time::Date,
+) -> sqlx::Result<Option<f64>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT ratio::FLOAT8 as "ratio!"
+        FROM (
+            SELECT ROUND(
+                cents(o.amount)::NUMERIC / NULLIF(cents(p.amount) + cents(o.amount), 0), 6
+            ) as ratio
+            FROM (
+                SELECT amount FROM incomes
+                WHERE who = $1 AND month <= $2
+                ORDER BY month DESC
+                LIMIT 1
+            ) p, (
+                SELECT amount FROM incomes
+                WHERE who <> $1 AND month <= $2
+                ORDER BY month DESC
+                LIMIT 1
+            ) o
+        ) _
+        WHERE ratio IS NOT NULL
+        "#,
+        payer as Person,
+        date
+    )
+    .fetch_optional(db)
+    .await
+}
@@ -0,0 +1,42 @@
+use super::Person;
+use sqlx::{Executor, Postgres};
+
+/// Marks a mail as sent for the period, telling whether this call was the first to do so.
+pub async fn claim(
+    db: impl Executor<'_, Database = Postgres>,
+    kind: &str,
+    who: Person,
+    period: &str,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        "
+        INSERT INTO mails_sent (kind, who, period, created_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT DO NOTHING
+        RETURNING kind
+        ",
+        kind,
+        who as Person,
+        period
+    )
+    .fetch_optional(db)
+    .await
+    .map(|r| r.is_some())
+}
+
+pub async fn unclaim(
+    db: impl Executor<'_, Database = Postgres>,
+    kind: &str,
+    who: Person,
+    period: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "DELETE FROM mails_sent WHERE kind = $1 AND who = $2 AND period = $3",
+        kind,
+        who as Person,
+        period
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
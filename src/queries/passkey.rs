@@ -0,0 +1,99 @@
+use super::Person;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{types::Json, Executor, Postgres};
+use webauthn_rs::prelude::Passkey;
+
+pub async fn all(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+) -> sqlx::Result<Vec<(i32, Passkey)>> {
+    sqlx::query!(
+        r#"
+        SELECT id, credential as "credential: Json<Passkey>"
+        FROM passkeys
+        WHERE who = $1
+        "#,
+        who as Person
+    )
+    .fetch_all(db)
+    .await
+    .map(|rs| rs.into_iter().map(|r| (r.id, r.credential.0)).collect())
+}
+
+pub async fn register(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+    passkey: &Passkey,
+) -> sqlx::Result<i32> {
+    sqlx::query_scalar!(
+        "
+        INSERT INTO passkeys (who, credential, created_at)
+        VALUES ($1, $2, NOW())
+        RETURNING id
+        ",
+        who as Person,
+        Json(passkey) as _
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn update(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    passkey: &Passkey,
+) -> sqlx::Result<()> {
+    sqlx::query_scalar!(
+        "
+        UPDATE passkeys
+        SET credential = $2
+        WHERE id = $1
+        RETURNING id
+        ",
+        id,
+        Json(passkey) as _
+    )
+    .fetch_one(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn begin(
+    db: impl Executor<'_, Database = Postgres>,
+    session_id: i32,
+    state: &(impl Serialize + Sync),
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO passkey_ceremonies (session_id, state, created_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (session_id) DO UPDATE
+        SET state = EXCLUDED.state, created_at = EXCLUDED.created_at
+        ",
+        session_id,
+        Json(state) as _
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn finish<T: DeserializeOwned>(
+    db: impl Executor<'_, Database = Postgres>,
+    session_id: i32,
+) -> sqlx::Result<Option<T>> {
+    sqlx::query!(
+        r#"
+        DELETE FROM passkey_ceremonies
+        WHERE session_id = $1
+        RETURNING state, created_at > NOW() - INTERVAL '5 minutes' as "fresh!"
+        "#,
+        session_id
+    )
+    .fetch_optional(db)
+    .await
+    .map(|r| {
+        r.filter(|r| r.fresh)
+            .and_then(|r| serde_json::from_value(r.state).ok())
+    })
+}
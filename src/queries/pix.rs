@@ -0,0 +1,45 @@
+use super::Person;
+use sqlx::{Executor, Postgres};
+
+pub struct PixKey {
+    pub key: String,
+    pub name: String,
+    pub city: String,
+}
+
+pub async fn get(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+) -> sqlx::Result<Option<PixKey>> {
+    sqlx::query_as!(
+        PixKey,
+        "SELECT key, name, city FROM pix_keys WHERE who = $1",
+        who as Person
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn set(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+    key: &str,
+    name: &str,
+    city: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO pix_keys (who, key, name, city, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (who) DO UPDATE
+        SET key = EXCLUDED.key, name = EXCLUDED.name, city = EXCLUDED.city, updated_at = NOW()
+        ",
+        who as Person,
+        key,
+        name,
+        city
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
@@ -0,0 +1,101 @@
+use super::Person;
+use sqlx::{Executor, Postgres};
+
+pub async fn subscribe(
+    db: impl Executor<'_, Database = Postgres>,
+    session_id: i32,
+    endpoint: &str,
+    p256dh: &str,
+    auth: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO push_subscriptions (session_id, endpoint, p256dh, auth, created_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (endpoint) DO UPDATE
+        SET session_id = EXCLUDED.session_id, p256dh = EXCLUDED.p256dh, auth = EXCLUDED.auth
+        ",
+        session_id,
+        endpoint,
+        p256dh,
+        auth
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn unsubscribe(
+    db: impl Executor<'_, Database = Postgres>,
+    session_id: i32,
+    endpoint: Option<&str>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        DELETE FROM push_subscriptions
+        WHERE session_id = $1 AND ($2::TEXT IS NULL OR endpoint = $2)
+        ",
+        session_id,
+        endpoint
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn expire(
+    db: impl Executor<'_, Database = Postgres>,
+    endpoint: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "DELETE FROM push_subscriptions WHERE endpoint = $1",
+        endpoint
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub struct Subscription {
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+}
+
+pub async fn subscriptions(
+    db: impl Executor<'_, Database = Postgres>,
+    who: Person,
+) -> sqlx::Result<Vec<Subscription>> {
+    sqlx::query_as!(
+        Subscription,
+        "
+        SELECT p.endpoint, p.p256dh, p.auth
+        FROM push_subscriptions p
+        JOIN sessions s ON s.id = p.session_id
+        WHERE s.who = $1
+        ",
+        who as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Marks the event as pushed, telling whether this call was the first to do so.
+/// Every backend instance hears the same notifications, but only one gets to push.
+pub async fn claim(
+    db: impl Executor<'_, Database = Postgres>,
+    event_id: i32,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        "
+        INSERT INTO push_sent (event_id, created_at)
+        VALUES ($1, NOW())
+        ON CONFLICT DO NOTHING
+        RETURNING event_id
+        ",
+        event_id
+    )
+    .fetch_optional(db)
+    .await
+    .map(|r| r.is_some())
+}
@@ -0,0 +1,59 @@
+use crate::exchange;
+use sqlx::{Executor, Postgres};
+
+/// The currency `paid`, `owed` and transfer amounts are kept in.
+pub const BASE: &str = "BRL";
+
+pub struct Rate {
+    pub currency: String,
+    pub day: time::Date,
+    pub rate: String,
+}
+
+pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec<Rate>> {
+    sqlx::query_as!(
+        Rate,
+        r#"
+        SELECT currency, day, rate::TEXT as "rate!"
+        FROM rates
+        ORDER BY currency, day DESC
+        "#
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn set(
+    db: impl Executor<'_, Database = Postgres>,
+    currency: &str,
+    day: time::Date,
+    rate: exchange::Rate,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        INSERT INTO rates (currency, day, rate, updated_at)
+        VALUES ($1, $2, $3::TEXT::NUMERIC, NOW())
+        ON CONFLICT (currency, day) DO UPDATE
+        SET rate = EXCLUDED.rate, updated_at = NOW()
+        ",
+        currency,
+        day,
+        rate.to_string()
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// The rate to convert amounts of `currency` dated `day` with. Nothing when none was
+/// recorded in the week up to that day.
+pub async fn rate_for(
+    db: impl Executor<'_, Database = Postgres>,
+    currency: &str,
+    day: time::Date,
+) -> sqlx::Result<Option<exchange::Rate>> {
+    sqlx::query_scalar!("SELECT rate_for($1, $2)::TEXT", currency, day)
+        .fetch_one(db)
+        .await
+        .map(|rate| rate.and_then(|r| r.parse().ok()))
+}
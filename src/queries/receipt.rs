@@ -0,0 +1,156 @@
+use super::Person;
+use crate::nfce::Nfce;
+use sqlx::{postgres::types::PgMoney, Executor, Postgres};
+
+pub struct Receipt {
+    pub id: i32,
+    pub emitter: String,
+    pub date: time::Date,
+    pub total: PgMoney,
+    pub expense_id: Option<i32>,
+}
+
+pub struct Item {
+    pub n: i32,
+    pub description: String,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: PgMoney,
+    pub total: PgMoney,
+}
+
+/// Importing the same document twice yields the receipt from the first time.
+pub async fn import(
+    db: impl Executor<'_, Database = Postgres>,
+    creator: Person,
+    nfce: &Nfce,
+) -> sqlx::Result<i32> {
+    let n = nfce.items.iter().map(|i| i.n).collect::<Vec<_>>();
+    let description = nfce
+        .items
+        .iter()
+        .map(|i| i.description.clone())
+        .collect::<Vec<_>>();
+    let quantity = nfce.items.iter().map(|i| i.quantity).collect::<Vec<_>>();
+    let unit = nfce
+        .items
+        .iter()
+        .map(|i| i.unit.clone())
+        .collect::<Vec<_>>();
+    let unit_price = nfce
+        .items
+        .iter()
+        .map(|i| PgMoney(i.unit_price))
+        .collect::<Vec<_>>();
+    let total = nfce
+        .items
+        .iter()
+        .map(|i| PgMoney(i.total))
+        .collect::<Vec<_>>();
+
+    sqlx::query_scalar!(
+        r#"
+        WITH inserted AS (
+            INSERT INTO receipts (access_key, creator, emitter, date, total, created_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (access_key) DO NOTHING
+            RETURNING id
+        ), items AS (
+            INSERT INTO receipt_items (receipt_id, n, description, quantity, unit, unit_price, total)
+            SELECT id, n, description, quantity, unit, unit_price, total
+            FROM inserted
+            CROSS JOIN unnest($6::INTEGER[], $7::TEXT[], $8::FLOAT8[], $9::TEXT[], $10::MONEY[], $11::MONEY[])
+                AS i(n, description, quantity, unit, unit_price, total)
+        )
+        SELECT id as "id!" FROM inserted
+        UNION ALL
+        SELECT id FROM receipts WHERE access_key = $1
+        "#,
+        nfce.key,
+        creator as Person,
+        nfce.emitter,
+        nfce.date,
+        PgMoney(nfce.total),
+        &n,
+        &description,
+        &quantity,
+        &unit,
+        &unit_price,
+        &total
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// A receipt backing the other person's personal expense isn't there for `viewer`.
+pub async fn get(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    id: i32,
+) -> sqlx::Result<Option<Receipt>> {
+    sqlx::query_as!(
+        Receipt,
+        "
+        SELECT id, emitter, date, total, expense_id
+        FROM receipts r
+        WHERE id = $1
+            AND NOT EXISTS (
+                SELECT FROM expenses e
+                WHERE e.id = r.expense_id
+                    AND e.split = 'Personal'
+                    AND e.creator <> $2
+            )
+        ",
+        id,
+        viewer as Person
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn items(
+    db: impl Executor<'_, Database = Postgres>,
+    viewer: Person,
+    id: i32,
+) -> sqlx::Result<Vec<Item>> {
+    sqlx::query_as!(
+        Item,
+        "
+        SELECT n, description, quantity, unit, unit_price, total
+        FROM receipt_items
+        WHERE receipt_id = $1
+            AND NOT EXISTS (
+                SELECT FROM receipts r
+                JOIN expenses e ON e.id = r.expense_id
+                WHERE r.id = $1
+                    AND e.split = 'Personal'
+                    AND e.creator <> $2
+            )
+        ORDER BY n
+        ",
+        id,
+        viewer as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// A receipt backs a single expense; false when it's taken or doesn't exist.
+pub async fn link(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    expense_id: i32,
+) -> sqlx::Result<bool> {
+    sqlx::query!(
+        "
+        UPDATE receipts
+        SET expense_id = $2
+        WHERE id = $1 AND expense_id IS NULL
+        ",
+        id,
+        expense_id
+    )
+    .execute(db)
+    .await
+    .map(|r| r.rows_affected() > 0)
+}
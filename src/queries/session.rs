@@ -14,6 +14,46 @@ pub async fn ask(db: impl Executor<'_, Database = Postgres>, person: Person) ->
     .await
 }
 
+/// Holds off other asks until the transaction ends, so counting and inserting can't interleave.
+pub async fn lock(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<()> {
+    sqlx::query!("LOCK TABLE sessions IN SHARE ROW EXCLUSIVE MODE")
+        .execute(db)
+        .await
+        .map(|_| ())
+}
+
+pub struct Recent {
+    pub outstanding: i64,
+    pub refused: i64,
+}
+
+/// Asks made within the last `minutes`. Replaced asks still count as outstanding,
+/// so asking over and over runs into the cap instead of staling the real one.
+pub async fn recent(
+    db: impl Executor<'_, Database = Postgres>,
+    person: Person,
+    minutes: i32,
+) -> sqlx::Result<Recent> {
+    sqlx::query!(
+        r#"
+        SELECT
+            COUNT(1) FILTER (WHERE confirmed_at IS NULL AND refused_at IS NULL) as "outstanding!",
+            COUNT(1) FILTER (WHERE refused_at IS NOT NULL) as "refused!"
+        FROM sessions
+        WHERE who = $1
+            AND created_at > NOW() - make_interval(mins => $2)
+        "#,
+        person as Person,
+        minutes
+    )
+    .fetch_one(db)
+    .await
+    .map(|r| Recent {
+        outstanding: r.outstanding,
+        refused: r.refused,
+    })
+}
+
 pub async fn confirm(db: impl Executor<'_, Database = Postgres>, id: i32) -> sqlx::Result<()> {
     sqlx::query_scalar!(
         "
@@ -65,7 +105,7 @@ pub async fn convert(db: impl Executor<'_, Database = Postgres>, id: i32) -> sql
     .map(|_| ())
 }
 
-#[derive(serde::Serialize)]
+#[derive(Debug, serde::Serialize)]
 pub enum SessionState {
     Confirmable,
     Convertable,
@@ -0,0 +1,212 @@
+use super::{Label, Person};
+use sqlx::{Executor, Postgres};
+
+/// One label in one month, in cents.
+pub struct Cell {
+    pub label: Label,
+    pub month: time::Date,
+    pub spent_me: i64,
+    pub spent_we: i64,
+    pub change_me: Option<i64>,
+    pub change_we: Option<i64>,
+    pub avg_me: f64,
+    pub avg_we: f64,
+    pub median_me: f64,
+    pub median_we: f64,
+}
+
+/// Label × month totals of confirmed expenses, counted like `/list` counts a month.
+/// Every label and every month from `from` to `to` gets a cell, empty ones counting as zero.
+/// Each cell also carries the mean and median of its label over the whole range.
+pub async fn matrix(
+    db: impl Executor<'_, Database = Postgres>,
+    me: Person,
+    from: time::Date,
+    to: time::Date,
+) -> sqlx::Result<Vec<Cell>> {
+    sqlx::query!(
+        r#"
+        WITH cells AS (
+            SELECT
+                l.label,
+                m.month::DATE as month,
+                COALESCE(SUM(CASE WHEN e.payer = $1 THEN e.paid - e.owed ELSE e.owed END), 0::money) as spent_me,
+                COALESCE(SUM(CASE WHEN e.split = 'Personal' THEN 0::money ELSE e.paid END), 0::money) as spent_we
+            FROM unnest(enum_range(NULL::label)) l(label)
+            CROSS JOIN generate_series(
+                date_trunc('month', $2::DATE),
+                date_trunc('month', $3::DATE),
+                INTERVAL '1 month'
+            ) m(month)
+            LEFT JOIN expenses e
+                ON e.label = l.label
+                AND e.confirmed_at IS NOT NULL
+                AND (e.split <> 'Personal' OR e.creator = $1)
+                AND date_trunc('month', e.date) = m.month
+            GROUP BY l.label, m.month
+        ), summary AS (
+            SELECT
+                label,
+                AVG(cents(spent_me))::FLOAT8 as avg_me,
+                AVG(cents(spent_we))::FLOAT8 as avg_we,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY cents(spent_me)) as median_me,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY cents(spent_we)) as median_we
+            FROM cells
+            GROUP BY label
+        )
+        SELECT
+            c.label as "label!: Label",
+            c.month as "month!",
+            c.spent_me as "spent_me!",
+            c.spent_we as "spent_we!",
+            c.spent_me - LAG(c.spent_me) OVER w as change_me,
+            c.spent_we - LAG(c.spent_we) OVER w as change_we,
+            s.avg_me as "avg_me!",
+            s.avg_we as "avg_we!",
+            s.median_me as "median_me!",
+            s.median_we as "median_we!"
+        FROM cells c
+        JOIN summary s USING (label)
+        WINDOW w AS (PARTITION BY c.label ORDER BY c.month)
+        ORDER BY c.label, c.month
+        "#,
+        me as Person,
+        from,
+        to
+    )
+    .fetch_all(db)
+    .await
+    .map(|rs| {
+        rs.into_iter()
+            .map(|r| Cell {
+                label: r.label,
+                month: r.month,
+                spent_me: r.spent_me.0,
+                spent_we: r.spent_we.0,
+                change_me: r.change_me.map(|c| c.0),
+                change_we: r.change_we.map(|c| c.0),
+                avg_me: r.avg_me,
+                avg_we: r.avg_we,
+                median_me: r.median_me,
+                median_we: r.median_we,
+            })
+            .collect()
+    })
+}
+
+/// Where one label stands this month, in cents.
+pub struct Outlook {
+    pub label: Label,
+    pub mtd_me: i64,
+    pub mtd_we: i64,
+    pub due_me: i64,
+    pub due_we: i64,
+    pub due: Vec<String>,
+    pub rate_me: f64,
+    pub rate_we: f64,
+    pub spread_me: f64,
+    pub spread_we: f64,
+}
+
+/// Month-to-date spend plus what history says about the rest of the month.
+///
+/// An expense is a recurring bill when its label and detail showed up in at least three
+/// of the last six months; the bills not seen yet this month are `due` at their last amount.
+/// Everything else makes up the daily run-rate of each past month, whose mean and standard
+/// deviation are the `rate` and `spread`.
+pub async fn outlook(
+    db: impl Executor<'_, Database = Postgres>,
+    me: Person,
+    today: time::Date,
+) -> sqlx::Result<Vec<Outlook>> {
+    sqlx::query_as!(
+        Outlook,
+        r#"
+        WITH spent AS (
+            SELECT
+                label,
+                date,
+                detail,
+                LOWER(TRIM(detail)) as key,
+                confirmed_at IS NOT NULL as confirmed,
+                cents(CASE WHEN payer = $1 THEN paid - owed ELSE owed END) as me,
+                CASE WHEN split = 'Personal' THEN 0 ELSE cents(paid) END as we
+            FROM expenses
+            WHERE refused_at IS NULL
+                AND (split <> 'Personal' OR creator = $1)
+        ), history AS (
+            SELECT generate_series(
+                GREATEST(
+                    date_trunc('month', $2::DATE) - INTERVAL '6 months',
+                    (SELECT date_trunc('month', MIN(date)) FROM spent WHERE confirmed)
+                ),
+                date_trunc('month', $2::DATE) - INTERVAL '1 month',
+                INTERVAL '1 month'
+            ) as month
+        ), recurring AS (
+            SELECT
+                label,
+                key,
+                (ARRAY_AGG(TRIM(detail) ORDER BY date DESC))[1] as detail,
+                (ARRAY_AGG(me ORDER BY date DESC))[1] as me,
+                (ARRAY_AGG(we ORDER BY date DESC))[1] as we
+            FROM spent
+            WHERE confirmed
+                AND key <> ''
+                AND date_trunc('month', date) IN (SELECT month FROM history)
+            GROUP BY label, key
+            HAVING COUNT(DISTINCT date_trunc('month', date)) >= 3
+        ), due AS (
+            SELECT r.label, r.detail, r.me, r.we
+            FROM recurring r
+            WHERE NOT EXISTS (
+                SELECT FROM spent s
+                WHERE s.label = r.label
+                    AND s.key = r.key
+                    AND date_trunc('month', s.date) = date_trunc('month', $2::DATE)
+            )
+        ), rates AS (
+            SELECT
+                l.label,
+                COALESCE(SUM(s.me), 0) / EXTRACT(DAY FROM h.month + INTERVAL '1 month - 1 day') as me,
+                COALESCE(SUM(s.we), 0) / EXTRACT(DAY FROM h.month + INTERVAL '1 month - 1 day') as we
+            FROM unnest(enum_range(NULL::label)) l(label)
+            CROSS JOIN history h
+            LEFT JOIN spent s
+                ON s.label = l.label
+                AND s.confirmed
+                AND date_trunc('month', s.date) = h.month
+                AND NOT EXISTS (
+                    SELECT FROM recurring r WHERE r.label = s.label AND r.key = s.key
+                )
+            GROUP BY l.label, h.month
+        )
+        SELECT
+            l.label as "label!: Label",
+            COALESCE((
+                SELECT SUM(me) FROM spent
+                WHERE label = l.label
+                    AND confirmed
+                    AND date_trunc('month', date) = date_trunc('month', $2::DATE)
+            ), 0)::BIGINT as "mtd_me!",
+            COALESCE((
+                SELECT SUM(we) FROM spent
+                WHERE label = l.label
+                    AND confirmed
+                    AND date_trunc('month', date) = date_trunc('month', $2::DATE)
+            ), 0)::BIGINT as "mtd_we!",
+            COALESCE((SELECT SUM(me) FROM due WHERE label = l.label), 0)::BIGINT as "due_me!",
+            COALESCE((SELECT SUM(we) FROM due WHERE label = l.label), 0)::BIGINT as "due_we!",
+            ARRAY(SELECT detail FROM due WHERE label = l.label ORDER BY detail) as "due!",
+            COALESCE((SELECT AVG(me) FROM rates WHERE label = l.label), 0)::FLOAT8 as "rate_me!",
+            COALESCE((SELECT AVG(we) FROM rates WHERE label = l.label), 0)::FLOAT8 as "rate_we!",
+            COALESCE((SELECT STDDEV_SAMP(me) FROM rates WHERE label = l.label), 0)::FLOAT8 as "spread_me!",
+            COALESCE((SELECT STDDEV_SAMP(we) FROM rates WHERE label = l.label), 0)::FLOAT8 as "spread_we!"
+        FROM unnest(enum_range(NULL::label)) l(label)
+        "#,
+        me as Person,
+        today
+    )
+    .fetch_all(db)
+    .await
+}
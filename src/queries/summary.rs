@@ -37,6 +37,42 @@ pub async fn total_owed(
     })
 }
 
+pub struct CurrencyOwed {
+    pub currency: String,
+    pub original: i64,
+    pub base: i64,
+}
+
+/// The foreign part of `total_owed`, confirmed or not, both in its own currency and in BRL.
+pub async fn currency_owed(
+    db: impl Executor<'_, Database = Postgres>,
+    by: Person,
+) -> sqlx::Result<Vec<CurrencyOwed>> {
+    sqlx::query_as!(
+        CurrencyOwed,
+        r#"
+        SELECT
+            currency as "currency!",
+            SUM(CASE WHEN payer = $1 THEN original ELSE -original END)::BIGINT as "original!",
+            SUM(CASE WHEN payer = $1 THEN owed ELSE -owed END)::BIGINT as "base!"
+        FROM (
+            SELECT currency, original_owed as original, cents(owed) as owed, payer
+            FROM expenses
+            WHERE refused_at IS NULL AND currency <> 'BRL'
+            UNION ALL
+            SELECT currency, original_amount, cents(amount), sender
+            FROM transfers
+            WHERE refused_at IS NULL AND currency <> 'BRL'
+        ) _
+        GROUP BY currency
+        ORDER BY currency
+        "#,
+        by as Person
+    )
+    .fetch_all(db)
+    .await
+}
+
 pub struct ResolvableCount {
     pub by_other: i64,
     pub by_you: i64,
@@ -66,3 +102,152 @@ pub async fn resolvable_count(
         by_you: r.by_you.unwrap_or_default(),
     })
 }
+
+/// Counts what's waiting on `me` and will be resolved automatically within `warn_days`.
+pub async fn expiring_count(
+    db: impl Executor<'_, Database = Postgres>,
+    me: Person,
+    days: i32,
+    warn_days: i32,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(1) as "count!"
+        FROM (
+        	SELECT creator, created_at FROM expenses WHERE confirmed_at IS NULL AND refused_at IS NULL
+        	UNION ALL
+        	SELECT sender, created_at FROM transfers WHERE confirmed_at IS NULL AND refused_at IS NULL
+        ) _
+        WHERE creator != $1
+            AND created_at < NOW() - make_interval(days => $2::INTEGER - $3::INTEGER)
+        "#,
+        me as Person,
+        days,
+        warn_days
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub struct MonthSpent {
+    pub spent_me: i64,
+    pub spent_we: i64,
+}
+
+/// Same totals as a month in `/list`: confirmed expenses only, transfers don't count.
+/// Personal expenses are only ever part of their creator's own spending.
+pub async fn month_spent(
+    db: impl Executor<'_, Database = Postgres>,
+    me: Person,
+    month: time::Date,
+) -> sqlx::Result<MonthSpent> {
+    sqlx::query!(
+        "
+        SELECT
+            SUM(CASE WHEN payer = $1 THEN paid - owed ELSE owed END) as spent_me,
+            SUM(CASE WHEN split = 'Personal' THEN 0::money ELSE paid END) as spent_we
+        FROM expenses
+        WHERE confirmed_at IS NOT NULL
+            AND date_trunc('month', date) = date_trunc('month', $2::DATE)
+        ",
+        me as Person,
+        month
+    )
+    .fetch_one(db)
+    .await
+    .map(|r| MonthSpent {
+        spent_me: r.spent_me.map(|a| a.0).unwrap_or(0),
+        spent_we: r.spent_we.map(|a| a.0).unwrap_or(0),
+    })
+}
+
+/// Net balance per person, positive when they're owed. Counts confirmed items, plus pending
+/// transfers so that a settlement already on its way isn't suggested again.
+/// What an expense's payer is owed is owed by the other person, never by everybody else.
+pub async fn balances(
+    db: impl Executor<'_, Database = Postgres>,
+) -> sqlx::Result<Vec<(Person, i64)>> {
+    let debts = sqlx::query!(
+        r#"
+        SELECT payer as "creditor!: Person", NULL::person as "debtor: Person", SUM(owed) as "amount!"
+        FROM expenses
+        WHERE confirmed_at IS NOT NULL
+        GROUP BY payer
+        UNION ALL
+        SELECT sender, receiver, SUM(amount)
+        FROM transfers
+        WHERE refused_at IS NULL
+        GROUP BY sender, receiver
+        "#
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut balances: Vec<(Person, i64)> = Vec::new();
+    for d in debts {
+        let debtor = d.debtor.unwrap_or_else(|| d.creditor.other());
+
+        for (who, amount) in [(d.creditor, d.amount.0), (debtor, -d.amount.0)] {
+            match balances.iter_mut().find(|(p, _)| *p == who) {
+                Some((_, balance)) => *balance += amount,
+                None => balances.push((who, amount)),
+            }
+        }
+    }
+
+    Ok(balances)
+}
+
+pub struct HistoryPoint {
+    pub period: time::Date,
+    pub definitely: i64,
+    pub maybe: i64,
+}
+
+/// Running balance as of the end of every `unit` ("day" or "month") that had any movement,
+/// split the same way as `total_owed`.
+pub async fn history(
+    db: impl Executor<'_, Database = Postgres>,
+    by: Person,
+    unit: &str,
+) -> sqlx::Result<Vec<HistoryPoint>> {
+    sqlx::query!(
+        r#"
+        SELECT
+            period as "period!",
+            SUM(SUM(CASE WHEN confirmed THEN signed ELSE 0::money END)) OVER w as "definitely!",
+            SUM(SUM(CASE WHEN NOT confirmed THEN signed ELSE 0::money END)) OVER w as "maybe!"
+        FROM (
+            SELECT
+                date_trunc($2, date)::DATE as period,
+                CASE WHEN payer = $1 THEN owed ELSE owed * -1 END as signed,
+                confirmed
+            FROM (
+                SELECT owed, payer, date, confirmed_at IS NOT NULL as confirmed
+                FROM expenses
+                WHERE refused_at IS NULL
+                UNION ALL
+                SELECT amount, sender, date, confirmed_at IS NOT NULL as confirmed
+                FROM transfers
+                WHERE refused_at IS NULL
+            ) _
+        ) _
+        GROUP BY period
+        WINDOW w AS (ORDER BY period)
+        ORDER BY period
+        "#,
+        by as Person,
+        unit
+    )
+    .fetch_all(db)
+    .await
+    .map(|rs| {
+        rs.into_iter()
+            .map(|r| HistoryPoint {
+                period: r.period,
+                definitely: r.definitely.0,
+                maybe: r.maybe.0,
+            })
+            .collect()
+    })
+}
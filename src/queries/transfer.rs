@@ -1,4 +1,5 @@
 use super::Person;
+use crate::exchange::Rate;
 use sqlx::{postgres::types::PgMoney, Executor, Postgres};
 use time::Date;
 
@@ -11,6 +12,9 @@ pub struct Transfer {
     pub confirmed_at: Option<time::OffsetDateTime>,
     pub refused_at: Option<time::OffsetDateTime>,
     pub created_at: time::OffsetDateTime,
+    pub currency: String,
+    pub original_amount: Option<i64>,
+    pub exchange_rate: Option<f64>,
 }
 
 pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec<Transfer>> {
@@ -25,7 +29,10 @@ pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec
             amount,
             confirmed_at,
             refused_at,
-            created_at
+            created_at,
+            currency,
+            original_amount,
+            exchange_rate::FLOAT8 as exchange_rate
         FROM transfers
         "#
     )
@@ -33,6 +40,34 @@ pub async fn all(db: impl Executor<'_, Database = Postgres>) -> sqlx::Result<Vec
     .await
 }
 
+pub async fn get(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+) -> sqlx::Result<Option<Transfer>> {
+    sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT
+            id,
+            sender as "sender: Person",
+            receiver as "receiver: Person",
+            date,
+            amount,
+            confirmed_at,
+            refused_at,
+            created_at,
+            currency,
+            original_amount,
+            exchange_rate::FLOAT8 as exchange_rate
+        FROM transfers
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(db)
+    .await
+}
+
 pub async fn resolvable(
     db: impl Executor<'_, Database = Postgres>,
     id: i32,
@@ -53,28 +88,80 @@ pub async fn resolvable(
     .await
 }
 
+/// Like expenses, a foreign `original_amount` comes converted into `amount` at `exchange_rate`.
+#[allow(clippy::too_many_arguments)]
 pub async fn submit(
     db: impl Executor<'_, Database = Postgres>,
     sender: Person,
     receiver: Person,
     date: Date,
     amount: i64,
+    currency: &str,
+    original_amount: Option<i64>,
+    exchange_rate: Option<Rate>,
 ) -> sqlx::Result<i32> {
     sqlx::query_scalar!(
         "
-        INSERT INTO transfers (sender, receiver, date, amount, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        INSERT INTO transfers (sender, receiver, date, amount, currency, original_amount, exchange_rate, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7::TEXT::NUMERIC, NOW())
         RETURNING id
         ",
         sender as Person,
         receiver as Person,
         date,
-        PgMoney(amount)
+        PgMoney(amount),
+        currency,
+        original_amount,
+        exchange_rate.map(|r| r.to_string())
     )
     .fetch_one(db)
     .await
 }
 
+pub struct Foreign {
+    pub date: Date,
+    pub currency: String,
+    pub original_amount: i64,
+}
+
+pub async fn foreign(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+) -> sqlx::Result<Option<Foreign>> {
+    sqlx::query_as!(
+        Foreign,
+        r#"
+        SELECT date, currency, original_amount as "original_amount!"
+        FROM transfers
+        WHERE id = $1 AND currency <> 'BRL'
+        "#,
+        id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn convert(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    amount: i64,
+    exchange_rate: Rate,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        UPDATE transfers
+        SET amount = $2, exchange_rate = $3::TEXT::NUMERIC
+        WHERE id = $1
+        ",
+        id,
+        PgMoney(amount),
+        exchange_rate.to_string()
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
 pub async fn confirm(
     db: impl Executor<'_, Database = Postgres>,
     id: i32,
@@ -120,3 +207,26 @@ pub async fn refuse(
     .await
     .map(|_| ())
 }
+
+/// Resolves, on nobody's behalf, everything left pending for longer than `days`.
+pub async fn expire(
+    db: impl Executor<'_, Database = Postgres>,
+    days: i32,
+    confirm: bool,
+) -> sqlx::Result<Vec<i32>> {
+    sqlx::query_scalar!(
+        "
+        UPDATE transfers
+        SET confirmed_at = CASE WHEN $2 THEN NOW() END,
+            refused_at = CASE WHEN $2 THEN NULL ELSE NOW() END
+        WHERE confirmed_at IS NULL
+            AND refused_at IS NULL
+            AND created_at < NOW() - make_interval(days => $1)
+        RETURNING id
+        ",
+        days,
+        confirm
+    )
+    .fetch_all(db)
+    .await
+}
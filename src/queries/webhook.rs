@@ -0,0 +1,148 @@
+use sqlx::{Executor, Postgres};
+
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub filter: Vec<String>,
+    pub exhausted: i64,
+    pub last_error: Option<String>,
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Along with each webhook, how many deliveries ran out of attempts and the latest error.
+pub async fn all(
+    db: impl Executor<'_, Database = Postgres>,
+    max_attempts: i32,
+) -> sqlx::Result<Vec<Webhook>> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT
+            w.id,
+            w.url,
+            w.filter,
+            COUNT(d.id) FILTER (WHERE d.attempts >= $1) as "exhausted!",
+            (
+                SELECT last_error
+                FROM webhook_deliveries
+                WHERE webhook_id = w.id AND delivered_at IS NULL AND last_error IS NOT NULL
+                ORDER BY id DESC
+                LIMIT 1
+            ) as last_error,
+            w.created_at
+        FROM webhooks w
+        LEFT JOIN webhook_deliveries d ON d.webhook_id = w.id AND d.delivered_at IS NULL
+        GROUP BY w.id
+        ORDER BY w.id
+        "#,
+        max_attempts
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn create(
+    db: impl Executor<'_, Database = Postgres>,
+    url: &str,
+    secret: &str,
+    filter: &[String],
+) -> sqlx::Result<i32> {
+    sqlx::query_scalar!(
+        "
+        INSERT INTO webhooks (url, secret, filter, created_at)
+        VALUES ($1, $2, $3, NOW())
+        RETURNING id
+        ",
+        url,
+        secret,
+        filter
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn delete(db: impl Executor<'_, Database = Postgres>, id: i32) -> sqlx::Result<bool> {
+    sqlx::query!("DELETE FROM webhooks WHERE id = $1", id)
+        .execute(db)
+        .await
+        .map(|r| r.rows_affected() > 0)
+}
+
+pub struct Delivery {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+}
+
+/// Leases due deliveries, so concurrent workers never pick the same ones.
+/// The lease has to outlast an attempt, or a slow one gets sent twice.
+pub async fn claim(
+    db: impl Executor<'_, Database = Postgres>,
+    limit: i64,
+    max_attempts: i32,
+    lease_secs: f64,
+) -> sqlx::Result<Vec<Delivery>> {
+    sqlx::query_as!(
+        Delivery,
+        r#"
+        UPDATE webhook_deliveries d
+        SET next_attempt_at = NOW() + make_interval(secs => $3)
+        FROM webhooks w
+        WHERE w.id = d.webhook_id
+            AND d.id IN (
+                SELECT id
+                FROM webhook_deliveries
+                WHERE delivered_at IS NULL
+                    AND attempts < $2
+                    AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+        RETURNING d.id, w.url, w.secret, d.payload, d.attempts
+        "#,
+        limit,
+        max_attempts,
+        lease_secs
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn delivered(db: impl Executor<'_, Database = Postgres>, id: i32) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        UPDATE webhook_deliveries
+        SET delivered_at = NOW(), attempts = attempts + 1, last_error = NULL
+        WHERE id = $1
+        ",
+        id
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Backs off exponentially: 10s, 20s, 40s... capped at a day.
+pub async fn failed(
+    db: impl Executor<'_, Database = Postgres>,
+    id: i32,
+    error: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "
+        UPDATE webhook_deliveries
+        SET attempts = attempts + 1,
+            last_error = $2,
+            next_attempt_at = NOW() + LEAST(INTERVAL '10 seconds' * POWER(2, attempts), INTERVAL '1 day')
+        WHERE id = $1
+        ",
+        id,
+        error
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
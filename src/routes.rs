@@ -1,12 +1,27 @@
+mod attachment;
+mod audit;
 mod expense;
+mod income;
 mod list;
+mod passkey;
+mod pix;
+mod push;
+mod rate;
+mod receipt;
 mod session;
+mod settle;
+mod stats;
+mod stream;
 mod summary;
 mod transfer;
+mod webhook;
+
+#[cfg(test)]
+pub(crate) mod test;
 
 use axum::{
-    extract::FromRef,
-    http::{Method, Request, Response},
+    extract::{DefaultBodyLimit, FromRef},
+    http::{header, HeaderName, Method, Request, Response},
     routing::{get, post},
 };
 use std::time::Duration;
@@ -17,36 +32,136 @@ type Db = axum::extract::State<sqlx::PgPool>;
 #[derive(Clone, FromRef)]
 pub(crate) struct State {
     key: axum_extra::extract::cookie::Key,
+    retired: crate::auth::RetiredKeys,
+    webauthn: Option<std::sync::Arc<webauthn_rs::Webauthn>>,
+    limiter: std::sync::Arc<crate::limit::RateLimiter>,
+    proxies: crate::limit::TrustedProxies,
+    lockout: crate::limit::Lockout,
+    notifications: tokio::sync::broadcast::Sender<crate::notify::Notification>,
+    push_key: Option<String>,
+    grace: Option<crate::grace::Grace>,
+    duplicate_days: expense::DuplicateDays,
+    attachments: crate::attachment::Store,
     db: sqlx::PgPool,
 }
 
-pub async fn init(db: sqlx::PgPool, env: crate::env::Env) -> anyhow::Result<()> {
-    let key = crate::auth::key(&env);
+pub async fn init(
+    db: sqlx::PgPool,
+    notifications: tokio::sync::broadcast::Sender<crate::notify::Notification>,
+    push_key: Option<String>,
+    grace: Option<crate::grace::Grace>,
+    attachments: crate::attachment::Store,
+    env: crate::env::Env,
+) -> anyhow::Result<()> {
+    let state = State {
+        key: crate::auth::key(&env),
+        retired: crate::auth::retired_keys(&env),
+        webauthn: crate::auth::webauthn(&env),
+        limiter: crate::limit::rate_limiter(&env),
+        proxies: crate::limit::trusted_proxies(&env),
+        lockout: crate::limit::lockout(&env),
+        notifications,
+        push_key,
+        grace,
+        duplicate_days: expense::DuplicateDays(env.duplicate_days),
+        attachments,
+        db,
+    };
+
+    axum::Server::bind(&env.rest_socket)
+        .serve(app(state, &env).into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await?;
 
+    Ok(())
+}
+
+fn app(state: State, env: &crate::env::Env) -> axum::Router {
     let cors = tower_http::cors::CorsLayer::new()
         .allow_methods([Method::GET, Method::POST])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            HeaderName::from_static(crate::auth::HEADER_CSRF),
+        ])
+        .expose_headers([HeaderName::from_static(crate::auth::HEADER_CSRF)])
         .allow_origin(env.allow_origin.clone())
         .allow_credentials(true);
 
-    let router = axum::Router::new()
+    let limit = axum::middleware::from_fn_with_state(state.clone(), crate::limit::layer);
+
+    axum::Router::new()
         .route("/", get(|| async { "oiblz" }))
-        .route("/session/ask/:who", post(session::ask))
+        .route("/session/ask/:who", post(session::ask).layer(limit.clone()))
         .route("/session/cancel", post(session::cancel))
         .route("/session/state", get(session::state))
-        .route("/session/confirm/:id", post(session::confirm))
-        .route("/session/refuse/:id", post(session::refuse))
+        .route("/session/watch", get(session::watch))
+        .route(
+            "/session/confirm/:id",
+            post(session::confirm).layer(limit.clone()),
+        )
+        .route(
+            "/session/refuse/:id",
+            post(session::refuse).layer(limit.clone()),
+        )
         .route("/session/convert", post(session::convert))
         .route("/session/confirmable", get(session::confirmable))
         .route("/session/drop", post(session::drop))
+        .route("/passkey/register/start", post(passkey::register_start))
+        .route("/passkey/register/finish", post(passkey::register_finish))
+        .route("/passkey/login/start", post(passkey::login_start))
+        .route(
+            "/passkey/login/finish",
+            post(passkey::login_finish).layer(limit),
+        )
         .route("/expense/submit", post(expense::submit))
         .route("/expense/confirm/:id", post(expense::confirm))
         .route("/expense/refuse/:id", post(expense::refuse))
         .route("/expense/splitrecc/:p/:l", get(expense::splitrecc))
+        .route("/expense/unusual/:p/:l/:paid", get(expense::unusual))
+        .route("/expense/recommend", get(expense::recommend))
+        .route("/expense/items/:id", get(expense::items))
+        .route("/duplicates", get(expense::duplicates))
         .route("/transfer/submit", post(transfer::submit))
         .route("/transfer/confirm/:id", post(transfer::confirm))
         .route("/transfer/refuse/:id", post(transfer::refuse))
+        .route("/transfer/pix/:id", get(pix::transfer))
+        .route("/transfer/pix/:id/svg", get(pix::transfer_svg))
+        .route("/transfer/pix/:id/png", get(pix::transfer_png))
+        .route("/pix/key", get(pix::get_key).post(pix::set_key))
+        .route(
+            "/attachment/upload/:entity/:id",
+            post(attachment::upload).layer(DefaultBodyLimit::max(
+                state.attachments.max_bytes + 64 * 1024,
+            )),
+        )
+        .route("/attachment/list/:entity/:id", get(attachment::list))
+        .route("/attachment/export", get(attachment::export))
+        .route("/attachment/:id", get(attachment::download))
+        .route("/attachment/delete/:id", post(attachment::delete))
+        .route("/receipt/import", post(receipt::import))
+        .route("/receipt/:id", get(receipt::get))
+        .route("/income", get(income::list).post(income::set))
+        .route("/rates", get(rate::list).post(rate::set))
+        .route("/rates/import", post(rate::import))
         .route("/summary", get(summary::get))
+        .route("/summary/history", get(summary::history))
+        .route("/stats", get(stats::get))
+        .route("/forecast", get(stats::forecast))
+        .route("/settle", get(settle::get))
+        .route("/settle/submit", post(settle::submit))
         .route("/list", post(list::generate))
+        .route("/audit", get(audit::get))
+        .route("/events", get(stream::events))
+        .route("/push/key", get(push::key))
+        .route("/push/subscribe", post(push::subscribe))
+        .route("/push/unsubscribe", post(push::unsubscribe))
+        .route("/webhook/list", get(webhook::list))
+        .route("/webhook/create", post(webhook::create))
+        .route("/webhook/delete/:id", post(webhook::delete))
+        .layer(axum::middleware::from_fn(crate::auth::csrf))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::auth::rotate,
+        ))
         .layer(cors)
         .layer(
             tower_http::trace::TraceLayer::new_for_http()
@@ -60,16 +175,10 @@ pub async fn init(db: sqlx::PgPool, env: crate::env::Env) -> anyhow::Result<()>
                     )
                 })
                 .on_response(|resp: &Response<_>, latency: Duration, span: &Span| {
-                    span.record("http.status_code", &tracing::field::display(resp.status()));
-                    span.record("latency", &tracing::field::debug(latency));
+                    span.record("http.status_code", tracing::field::display(resp.status()));
+                    span.record("latency", tracing::field::debug(latency));
                     tracing::info!("!")
                 }),
         )
-        .with_state(State { db, key });
-
-    axum::Server::bind(&env.rest_socket)
-        .serve(router.into_make_service())
-        .await?;
-
-    Ok(())
+        .with_state(state)
 }
@@ -0,0 +1,383 @@
+use super::Db;
+use crate::{
+    attachment::Store,
+    auth::Session,
+    queries::{event::Entity, Person},
+};
+use axum::{
+    body::StreamBody,
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::{stream, StreamExt, TryFutureExt};
+use serde::Serialize;
+use std::{collections::BTreeSet, ops::Deref};
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+pub struct Attachment {
+    id: i32,
+    entity: Entity,
+    entity_id: i32,
+    yours: bool,
+    name: String,
+    mime: String,
+    size: i32,
+    sha256: String,
+    created_at: String,
+}
+
+pub async fn list(
+    db: Db,
+    s: Session,
+    Path((entity, entity_id)): Path<(Entity, i32)>,
+) -> Result<Json<Vec<Attachment>>, StatusCode> {
+    match crate::queries::attachment::of(db.deref(), s.who, entity, entity_id).await {
+        Ok(atts) => Ok(Json(atts.into_iter().map(|a| view(a, s.who)).collect())),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn view(a: crate::queries::attachment::Attachment, who: Person) -> Attachment {
+    Attachment {
+        id: a.id,
+        entity: a.entity,
+        entity_id: a.entity_id,
+        yours: a.creator == who,
+        name: a.name,
+        mime: a.mime,
+        size: a.size,
+        sha256: a.sha256,
+        created_at: a.created_at.format(&Iso8601::DEFAULT).unwrap_or_default(),
+    }
+}
+
+/// A tar of everything the viewer can see: `attachments.json` lists the attachments,
+/// and each blob follows once as `blobs/<sha256>`, read only as the archive is sent.
+pub async fn export(
+    db: Db,
+    State(store): State<Store>,
+    s: Session,
+) -> Result<Response, StatusCode> {
+    let attachments = match crate::queries::attachment::all(db.deref(), s.who).await {
+        Ok(atts) => atts.into_iter().map(|a| view(a, s.who)).collect::<Vec<_>>(),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let manifest = serde_json::to_vec_pretty(&attachments).map_err(|e| {
+        tracing::error!("{e:?}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let blobs = attachments
+        .into_iter()
+        .map(|a| a.sha256)
+        .collect::<BTreeSet<_>>();
+
+    let entries =
+        stream::once(
+            async move { Ok(crate::attachment::tar_entry("attachments.json", &manifest)) },
+        )
+        .chain(stream::iter(blobs).then(move |sha256| {
+            let store = store.clone();
+            async move {
+                store
+                    .get(&sha256)
+                    .await
+                    .map(|bytes| crate::attachment::tar_entry(&format!("blobs/{sha256}"), &bytes))
+                    .map_err(|e| {
+                        tracing::error!("{e:?}");
+                        e
+                    })
+            }
+        }))
+        .chain(stream::once(async {
+            Ok(crate::attachment::TAR_END.to_vec())
+        }));
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/x-tar"),
+        (
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"attachments.tar\"",
+        ),
+    ];
+
+    Ok((headers, StreamBody::new(entries)).into_response())
+}
+
+/// Takes the first `file` field of the form; the type is sniffed from its content.
+pub async fn upload(
+    db: Db,
+    State(store): State<Store>,
+    s: Session,
+    Path((entity, entity_id)): Path<(Entity, i32)>,
+    mut form: Multipart,
+) -> Result<Json<i32>, StatusCode> {
+    let mut field = loop {
+        match form.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => break field,
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return Err(StatusCode::BAD_REQUEST),
+        }
+    };
+
+    let name = field.file_name().unwrap_or("attachment").to_owned();
+    let mut bytes = Vec::new();
+
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) if bytes.len() + chunk.len() > store.max_bytes => {
+                return Err(StatusCode::PAYLOAD_TOO_LARGE)
+            }
+            Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(_) => return Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    let Some(mime) = crate::attachment::sniff(&bytes) else {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    };
+
+    let name = name.chars().take(255).collect::<String>();
+
+    let sha256 = crate::attachment::digest(&bytes);
+
+    let res = db.begin().and_then(|mut transaction| async move {
+        if !crate::queries::attachment::attachable(&mut transaction, s.who, entity, entity_id)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        crate::queries::attachment::reference(&mut transaction, &sha256).await?;
+
+        if let Err(e) = store.put(&sha256, &bytes).await {
+            tracing::error!("{e:?}");
+            return Ok(Some(Err(StatusCode::INTERNAL_SERVER_ERROR)));
+        }
+
+        let id = crate::queries::attachment::create(
+            &mut transaction,
+            entity,
+            entity_id,
+            s.who,
+            &name,
+            mime,
+            bytes.len() as i32,
+            &sha256,
+        )
+        .await?;
+
+        transaction.commit().await.map(|()| Some(Ok(id)))
+    });
+
+    match res.await {
+        Ok(Some(id)) => id.map(Json),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn download(
+    db: Db,
+    State(store): State<Store>,
+    s: Session,
+    Path(id): Path<i32>,
+) -> Result<Response, StatusCode> {
+    let attachment = match crate::queries::attachment::get(db.deref(), s.who, id).await {
+        Ok(Some(a)) => a,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let bytes = store.get(&attachment.sha256).await.map_err(|e| {
+        tracing::error!("{e:?}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let name = attachment
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && *c != '"' && *c != '\\' || *c == ' ')
+        .collect::<String>();
+
+    let headers = [
+        (header::CONTENT_TYPE, attachment.mime),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{name}\""),
+        ),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_owned()),
+    ];
+
+    Ok((headers, bytes).into_response())
+}
+
+/// The blob goes too once nothing points at it, before its row is let go.
+pub async fn delete(
+    db: Db,
+    State(store): State<Store>,
+    s: Session,
+    Path(id): Path<i32>,
+) -> StatusCode {
+    // The blob is removed only after the commit, so a rollback can't leave attachments
+    // pointing at a missing file.
+    let res = db.begin().and_then(|mut transaction| async move {
+        let Some(sha256) = crate::queries::attachment::delete(&mut transaction, id, s.who).await?
+        else {
+            return Ok(None);
+        };
+
+        let orphan = crate::queries::attachment::dereference(&mut transaction, &sha256).await?;
+        transaction
+            .commit()
+            .await
+            .map(|()| Some(orphan.then_some(sha256)))
+    });
+
+    match res.await {
+        Ok(Some(orphan)) => {
+            if let Some(sha256) = orphan {
+                if let Err(e) = store.remove(&sha256).await {
+                    tracing::error!("{e:?}");
+                }
+            }
+            StatusCode::OK
+        }
+        Ok(None) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+    use axum::{
+        body::Body,
+        http::{header, Method, StatusCode},
+    };
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n receipt";
+
+    async fn upload(client: &mut test::Client, expense: i32) -> test::Reply {
+        let body = [
+            b"--x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"r.png\"\r\n\r\n"
+                .as_slice(),
+            PNG,
+            b"\r\n--x--\r\n",
+        ]
+        .concat();
+
+        let req = client
+            .request(
+                Method::POST,
+                &format!("/attachment/upload/Expense/{expense}"),
+            )
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=x")
+            .body(Body::from(body))
+            .unwrap();
+
+        client.send(req).await
+    }
+
+    #[sqlx::test]
+    async fn keeps_a_blob_while_anything_points_at_it(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+        let id = test::expense(&db, "Market", 1000, None).await;
+
+        let first = upload(&mut ale, id).await.json();
+        let second = upload(&mut ale, id).await.json();
+        let refs = "SELECT refs FROM attachment_blobs";
+        assert_eq!(
+            sqlx::query_scalar::<_, i32>(refs)
+                .fetch_one(&db)
+                .await
+                .unwrap(),
+            2
+        );
+
+        let delete = format!("/attachment/delete/{first}");
+        assert_eq!(
+            ale.post(&delete, serde_json::Value::Null).await.status,
+            StatusCode::OK
+        );
+        let kept = ale.get(&format!("/attachment/{second}")).await;
+        assert_eq!(kept.status, StatusCode::OK);
+        assert_eq!(kept.body, PNG);
+
+        let delete = format!("/attachment/delete/{second}");
+        assert_eq!(
+            ale.post(&delete, serde_json::Value::Null).await.status,
+            StatusCode::OK
+        );
+        assert_eq!(
+            sqlx::query_scalar::<_, i32>(refs)
+                .fetch_one(&db)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(upload(&mut ale, id).await.status, StatusCode::OK);
+        let third = ale
+            .get(&format!("/attachment/list/Expense/{id}"))
+            .await
+            .json()[0]["id"]
+            .clone();
+        assert_eq!(ale.get(&format!("/attachment/{third}")).await.body, PNG);
+    }
+
+    #[sqlx::test]
+    async fn exports_each_blob_once(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+        let id = test::expense(&db, "Market", 1000, None).await;
+
+        upload(&mut ale, id).await;
+        upload(&mut ale, id).await;
+
+        let tar = ale.get("/attachment/export").await;
+        assert_eq!(tar.status, StatusCode::OK);
+
+        let mut entries = Vec::new();
+        let mut rest = tar.body.as_slice();
+        while rest.len() >= 512 && rest[0] != 0 {
+            let name = std::str::from_utf8(&rest[..100])
+                .unwrap()
+                .trim_end_matches('\0');
+            let size = std::str::from_utf8(&rest[124..135]).unwrap();
+            let size = usize::from_str_radix(size, 8).unwrap();
+            entries.push((name.to_owned(), rest[512..512 + size].to_vec()));
+            rest = &rest[512 + size.div_ceil(512) * 512..];
+        }
+        assert_eq!(rest, [0; 1024]);
+
+        let sha256 = crate::attachment::digest(PNG);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "attachments.json");
+        let manifest = serde_json::from_slice::<serde_json::Value>(&entries[0].1).unwrap();
+        assert_eq!(manifest.as_array().unwrap().len(), 2);
+        assert_eq!(manifest[1]["sha256"], sha256);
+        assert_eq!(entries[1], (format!("blobs/{sha256}"), PNG.to_vec()));
+    }
+}
@@ -0,0 +1,67 @@
+use super::Db;
+use crate::{
+    auth::Session,
+    queries::{event::Entity, Person},
+};
+use axum::{extract::Query, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+struct Event {
+    id: i32,
+    actor: Option<Person>,
+    session_id: Option<i32>,
+    entity: Entity,
+    entity_id: i32,
+    old_state: Option<String>,
+    new_state: String,
+    meta: serde_json::Value,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    events: Vec<Event>,
+    next: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct Page {
+    before: Option<i32>,
+    limit: Option<i64>,
+}
+
+pub async fn get(db: Db, s: Session, p: Query<Page>) -> Result<Json<Response>, StatusCode> {
+    let limit = p.limit.unwrap_or(50).clamp(1, 200);
+
+    let events = crate::queries::event::page(db.deref(), s.who, p.before, limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let next = match events.last() {
+        Some(e) if events.len() as i64 == limit => Some(e.id),
+        _ => None,
+    };
+
+    let events = events
+        .into_iter()
+        .map(|e| Event {
+            id: e.id,
+            actor: e.actor,
+            session_id: e.session_id,
+            entity: e.entity,
+            entity_id: e.entity_id,
+            old_state: e.old_state,
+            new_state: e.new_state,
+            meta: e.meta,
+            created_at: e.created_at.format(&Iso8601::DEFAULT).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(Json(Response { events, next }))
+}
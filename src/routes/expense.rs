@@ -1,12 +1,18 @@
-use super::Db;
+use super::{list::date_to_string, rate::foreign, Db};
 use crate::{
-    auth::Session,
-    queries::{Label, Person, Split},
+    auth::{Meta, Session},
+    queries::{event::Entity, rate::BASE, Label, Person, Split},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
 };
-use axum::{extract::Path, http::StatusCode, Json};
 use futures::TryFutureExt;
-use serde::Deserialize;
-use std::ops::Deref;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, ops::Deref};
 use time::format_description::well_known::Iso8601;
 
 #[derive(Deserialize)]
@@ -18,56 +24,270 @@ pub struct SubmitRequest {
     date: String,
     paid: i64,
     owed: Option<i64>,
+    #[serde(default)]
+    force: bool,
+    receipt: Option<i32>,
+    items: Option<Vec<ItemRequest>>,
+    currency: Option<String>,
+}
+
+/// Who an item is entirely for, as seen from the payer.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub enum Only {
+    Payer,
+    Other,
 }
 
-pub async fn submit(db: Db, s: Session, r: Json<SubmitRequest>) -> StatusCode {
+#[derive(Deserialize)]
+pub struct ItemRequest {
+    label: Option<Label>,
+    detail: Option<String>,
+    amount: i64,
+    split: Option<Split>,
+    only: Option<Only>,
+    owed: Option<i64>,
+}
+
+#[derive(Clone, Copy)]
+pub struct DuplicateDays(pub i32);
+
+#[derive(Serialize, Clone)]
+pub struct Duplicate {
+    id: i32,
+    yours: bool,
+    detail: Option<String>,
+    date: String,
+    paid: i64,
+    confirmed: bool,
+}
+
+#[derive(Serialize)]
+pub struct SubmitResponse {
+    id: i32,
+    unusual: bool,
+}
+
+/// Answers `409` with the possible duplicates unless the request insists with `force`.
+pub async fn submit(
+    db: Db,
+    State(DuplicateDays(days)): State<DuplicateDays>,
+    s: Session,
+    meta: Meta,
+    mut r: Json<SubmitRequest>,
+) -> Result<Response, StatusCode> {
     let date = match time::Date::parse(&r.date, &Iso8601::DEFAULT) {
-        Err(_) => return StatusCode::BAD_REQUEST,
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
         Ok(data) => data,
     };
 
-    let owed = match (r.split, r.owed) {
-        (Split::Arbitrary, Some(owed)) if owed <= r.paid => owed,
-        (Split::Proportional2to1, None) => match r.payer {
-            Person::Ale => r.paid / 3,
-            Person::Lu => r.paid * 2 / 3,
-        },
-        (Split::Proportional3to2, None) => match r.payer {
-            Person::Ale => r.paid * 2 / 5,
-            Person::Lu => r.paid * 3 / 5,
+    let items = match (r.split, r.items.take()) {
+        (Split::Itemized, Some(items)) if !items.is_empty() => items
+            .into_iter()
+            .map(|i| {
+                let owed = match (i.split, i.only) {
+                    (None, Some(Only::Payer)) if i.owed.is_none() => 0,
+                    (None, Some(Only::Other)) if i.owed.is_none() => i.amount,
+                    (Some(Split::Itemized | Split::Personal | Split::ProportionalIncome), None) => {
+                        return None
+                    }
+                    (Some(split), None) => split.owed(r.payer, i.amount, i.owed)?,
+                    _ => return None,
+                };
+
+                let only = i.only.map(|o| match (o, r.payer) {
+                    (Only::Payer, payer) => payer,
+                    (Only::Other, Person::Ale) => Person::Lu,
+                    (Only::Other, Person::Lu) => Person::Ale,
+                });
+
+                (i.amount >= 0).then_some((i, only, owed))
+            })
+            .collect::<Option<Vec<_>>>(),
+        (Split::Itemized, _) | (_, Some(_)) => None,
+        (_, None) => Some(Vec::new()),
+    };
+
+    let Some(items) = items else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let owed = match r.split {
+        Split::Itemized if items.iter().map(|i| i.0.amount).sum::<i64>() == r.paid => {
+            Some(items.iter().map(|i| i.2).sum())
+        }
+        Split::Itemized => None,
+        Split::Personal if r.payer != s.who => None,
+        // Worked out from incomes once inside the transaction.
+        Split::ProportionalIncome if r.owed.is_none() => Some(0),
+        split => split.owed(r.payer, r.paid, r.owed),
+    };
+
+    let Some(owed) = owed else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    // `paid` and `owed` are in `currency` when it's foreign, converted below.
+    let currency = match (r.currency.as_deref(), r.split) {
+        (None | Some(BASE), _) => None,
+        (Some(_), Split::Itemized) => return Err(StatusCode::BAD_REQUEST),
+        (Some(c), _) => match foreign(c) {
+            Some(c) => Some(c.to_owned()),
+            None => return Err(StatusCode::BAD_REQUEST),
         },
-        (Split::Evenly, None) => r.paid / 2,
-        _ => return StatusCode::BAD_REQUEST,
     };
 
-    match crate::queries::expense::submit(
-        db.deref(),
-        s.who,
-        r.payer,
-        r.split,
-        r.label,
-        r.detail.as_deref(),
-        date,
-        r.paid,
-        owed,
-    )
-    .await
-    {
-        Ok(_) => StatusCode::OK,
+    let res = db.begin().and_then(|mut transaction| async move {
+        if !r.force {
+            let duplicates = crate::queries::expense::duplicates_of(
+                &mut transaction,
+                s.who,
+                r.payer,
+                currency.as_deref().unwrap_or(BASE),
+                r.paid,
+                date,
+                r.detail.as_deref(),
+                days,
+            )
+            .await?;
+
+            if !duplicates.is_empty() {
+                let duplicates = duplicates
+                    .into_iter()
+                    .map(|c| Duplicate {
+                        id: c.id,
+                        yours: c.creator == s.who,
+                        detail: c.detail,
+                        date: date_to_string(c.date),
+                        paid: c.paid,
+                        confirmed: c.confirmed,
+                    })
+                    .collect::<Vec<_>>();
+
+                return Ok((StatusCode::CONFLICT, Json(duplicates)).into_response());
+            }
+        }
+
+        let ratio = match r.split {
+            Split::ProportionalIncome => {
+                match crate::queries::income::ratio(&mut transaction, r.payer, date).await? {
+                    Some(ratio) => Some(ratio),
+                    None => return Ok(StatusCode::BAD_REQUEST.into_response()),
+                }
+            }
+            _ => None,
+        };
+
+        let owed = ratio.map_or(owed, |ratio| crate::exchange::share(r.paid, ratio));
+
+        let (paid, owed, original) = match &currency {
+            Some(c) => match crate::queries::rate::rate_for(&mut transaction, c, date).await? {
+                Some(rate) => {
+                    let paid = rate.convert(r.paid);
+                    match crate::exchange::owed(r.split, r.payer, paid, rate.convert(owed), ratio) {
+                        Some(base_owed) => (paid, base_owed, Some((r.paid, owed, rate))),
+                        None => return Ok(StatusCode::BAD_REQUEST.into_response()),
+                    }
+                }
+                None => return Ok(StatusCode::BAD_REQUEST.into_response()),
+            },
+            None => (r.paid, owed, None),
+        };
+
+        let unusual = crate::queries::expense::fences(&mut transaction, s.who, r.payer, r.label)
+            .await?
+            .is_some_and(|f| !f.contain(paid));
+
+        let id = crate::queries::expense::submit(
+            &mut transaction,
+            s.who,
+            r.payer,
+            r.split,
+            r.label,
+            r.detail.as_deref(),
+            date,
+            paid,
+            owed,
+            ratio,
+            unusual,
+            currency.as_deref().unwrap_or(BASE),
+            original.map(|o| o.0),
+            original.map(|o| o.1),
+            original.map(|o| o.2),
+        )
+        .await?;
+
+        for (n, (item, only, owed)) in (1..).zip(items) {
+            crate::queries::expense::submit_item(
+                &mut transaction,
+                id,
+                n,
+                r.payer,
+                item.label,
+                item.detail.as_deref(),
+                item.amount,
+                item.split,
+                only,
+                owed,
+            )
+            .await?;
+        }
+
+        if let Some(receipt) = r.receipt {
+            if !crate::queries::receipt::link(&mut transaction, receipt, id).await? {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            }
+        }
+
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Expense,
+            id,
+            None,
+            match r.split {
+                Split::Personal => "Confirmed",
+                _ => "Pending",
+            },
+            &meta,
+        )
+        .await?;
+
+        transaction
+            .commit()
+            .await
+            .map(|()| Json(SubmitResponse { id, unusual }).into_response())
+    });
+
+    match res.await {
+        Ok(r) => Ok(r),
         Err(e) => {
             tracing::error!("{e:?}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
-pub async fn confirm(db: Db, s: Session, id: Path<i32>) -> StatusCode {
+pub async fn confirm(db: Db, s: Session, meta: Meta, id: Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         if !crate::queries::expense::resolvable(&mut transaction, *id, s.who).await? {
             return Ok(None);
         };
 
         crate::queries::expense::confirm(&mut transaction, *id, s.who).await?;
+        crate::exchange::expense(&mut transaction, *id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Expense,
+            *id,
+            Some("Pending"),
+            "Confirmed",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().map_ok(Some).await
     });
 
@@ -81,13 +301,25 @@ pub async fn confirm(db: Db, s: Session, id: Path<i32>) -> StatusCode {
     }
 }
 
-pub async fn refuse(db: Db, s: Session, id: Path<i32>) -> StatusCode {
+pub async fn refuse(db: Db, s: Session, meta: Meta, id: Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         if !crate::queries::expense::resolvable(&mut transaction, *id, s.who).await? {
             return Ok(None);
         };
 
         crate::queries::expense::refuse(&mut transaction, *id, s.who).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Expense,
+            *id,
+            Some("Pending"),
+            "Refused",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().map_ok(Some).await
     });
 
@@ -103,10 +335,10 @@ pub async fn refuse(db: Db, s: Session, id: Path<i32>) -> StatusCode {
 
 pub async fn splitrecc(
     db: Db,
-    _s: Session,
+    s: Session,
     Path((payer, label)): Path<(Person, Label)>,
 ) -> Result<Json<Option<Split>>, StatusCode> {
-    match crate::queries::expense::splitrecc(db.deref(), payer, label).await {
+    match crate::queries::expense::splitrecc(db.deref(), s.who, payer, label).await {
         Ok(sr) => Ok(Json(sr)),
         Err(e) => {
             tracing::error!("{e:?}");
@@ -114,3 +346,309 @@ pub async fn splitrecc(
         }
     }
 }
+
+#[derive(Deserialize)]
+pub struct RecommendRequest {
+    payer: Person,
+    detail: String,
+    paid: i64,
+}
+
+#[derive(Serialize)]
+pub struct Recommendation {
+    label: Label,
+    label_confidence: f64,
+    split: Option<Split>,
+    split_confidence: f64,
+    owed: Option<i64>,
+}
+
+/// Confidences count one phantom vote against the winner,
+/// so a single past match never comes across as a sure thing.
+pub async fn recommend(
+    db: Db,
+    s: Session,
+    r: Query<RecommendRequest>,
+) -> Result<Json<Option<Recommendation>>, StatusCode> {
+    let votes = crate::queries::expense::votes(db.deref(), s.who, r.payer, &r.detail, r.paid)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let total = votes.iter().map(|v| v.weight).sum::<f64>();
+
+    let best = votes
+        .iter()
+        .map(|v| v.label)
+        .unique()
+        .map(|l| {
+            let weight = votes.iter().filter(|v| v.label == l).map(|v| v.weight);
+            (l, weight.sum::<f64>())
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    let Some((label, weight)) = best else {
+        return Ok(Json(None));
+    };
+
+    let splits = votes
+        .iter()
+        .filter(|v| v.label == label && v.weight_payer > 0.0);
+
+    let split = splits
+        .clone()
+        .max_by(|a, b| a.weight_payer.total_cmp(&b.weight_payer));
+
+    let split_total = splits.map(|v| v.weight_payer).sum::<f64>();
+
+    Ok(Json(Some(Recommendation {
+        label,
+        label_confidence: weight / (total + 1.0),
+        split: split.map(|v| v.split),
+        split_confidence: split.map_or(0.0, |v| v.weight_payer / (split_total + 1.0)),
+        owed: split
+            .filter(|v| matches!(v.split, Split::Arbitrary))
+            .and_then(|v| v.owed_ratio)
+            .map(|ratio| (ratio * r.paid as f64).round() as i64),
+    })))
+}
+
+#[derive(Serialize)]
+pub struct UnusualResponse {
+    unusual: bool,
+    low: f64,
+    high: f64,
+}
+
+pub async fn unusual(
+    db: Db,
+    s: Session,
+    Path((payer, label, paid)): Path<(Person, Label, i64)>,
+) -> Result<Json<Option<UnusualResponse>>, StatusCode> {
+    match crate::queries::expense::fences(db.deref(), s.who, payer, label).await {
+        Ok(f) => Ok(Json(f.map(|f| UnusualResponse {
+            unusual: !f.contain(paid),
+            low: f.low,
+            high: f.high,
+        }))),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn duplicates(
+    db: Db,
+    State(DuplicateDays(days)): State<DuplicateDays>,
+    s: Session,
+) -> Result<Json<Vec<[Duplicate; 2]>>, StatusCode> {
+    let (pairs, expenses) = db
+        .begin()
+        .and_then(|mut transaction| async move {
+            Ok((
+                crate::queries::expense::duplicates(&mut transaction, s.who, days).await?,
+                crate::queries::expense::all(&mut transaction).await?,
+            ))
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let expenses = expenses
+        .into_iter()
+        .map(|e| {
+            let d = Duplicate {
+                id: e.id,
+                yours: e.creator == s.who,
+                detail: e.detail,
+                date: date_to_string(e.date),
+                paid: e.paid.0,
+                confirmed: e.confirmed_at.is_some(),
+            };
+            (e.id, d)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let pairs = pairs
+        .into_iter()
+        .filter_map(|(a, b)| Some([expenses.get(&a)?.clone(), expenses.get(&b)?.clone()]))
+        .collect();
+
+    Ok(Json(pairs))
+}
+
+#[derive(Serialize)]
+pub struct Item {
+    n: i32,
+    label: Option<Label>,
+    detail: Option<String>,
+    amount: i64,
+    split: Option<Split>,
+    only: Option<Only>,
+    owed: i64,
+}
+
+pub async fn items(db: Db, s: Session, Path(id): Path<i32>) -> Result<Json<Vec<Item>>, StatusCode> {
+    match crate::queries::expense::items(db.deref(), s.who, id).await {
+        Ok(items) => Ok(Json(
+            items
+                .into_iter()
+                .map(|i| Item {
+                    n: i.n,
+                    label: i.label,
+                    detail: i.detail,
+                    amount: i.amount.0,
+                    split: i.split,
+                    only: i.only.map(|o| match o == i.payer {
+                        true => Only::Payer,
+                        false => Only::Other,
+                    }),
+                    owed: i.owed.0,
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    #[sqlx::test]
+    async fn tolerates_a_bill_that_never_changed(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        for _ in 0..6 {
+            test::expense(&db, "Internet", 10000, None).await;
+        }
+
+        let unusual = |paid: i64| format!("/expense/unusual/Ale/Internet/{paid}");
+        assert_eq!(ale.get(&unusual(10001)).await.json()["unusual"], false);
+        assert_eq!(ale.get(&unusual(12000)).await.json()["unusual"], false);
+        assert_eq!(ale.get(&unusual(20000)).await.json()["unusual"], true);
+    }
+
+    #[sqlx::test]
+    async fn catches_the_same_amount_twice(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, mut lu) = test::sessions(&app).await;
+
+        let expense = json!({
+            "payer": "Ale",
+            "split": "Evenly",
+            "label": "Market",
+            "detail": "groceries",
+            "date": "2026-10-10",
+            "paid": 4321,
+        });
+
+        assert_eq!(
+            ale.post("/expense/submit", expense.clone()).await.status,
+            StatusCode::OK
+        );
+
+        let again = lu.post("/expense/submit", expense.clone()).await;
+        assert_eq!(again.status, StatusCode::CONFLICT);
+        assert_eq!(again.json()[0]["paid"], 4321);
+
+        let mut forced = expense;
+        forced["force"] = json!(true);
+        assert_eq!(
+            lu.post("/expense/submit", forced).await.status,
+            StatusCode::OK
+        );
+
+        let pairs = ale.get("/duplicates").await.json();
+        assert_eq!(pairs[0][0]["paid"], 4321);
+        assert_eq!(pairs[0][1]["paid"], 4321);
+    }
+
+    #[sqlx::test]
+    async fn keeps_personal_expenses_out_of_the_others_sight(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, mut lu) = test::sessions(&app).await;
+        crate::queries::webhook::create(&db, "https://localhost/hook", "hush", &[])
+            .await
+            .unwrap();
+
+        for _ in 0..5 {
+            let personal = json!({
+                "payer": "Ale",
+                "split": "Personal",
+                "label": "Market",
+                "detail": "snacks",
+                "date": "2026-10-10",
+                "paid": 1000,
+                "force": true,
+            });
+            assert_eq!(
+                ale.post("/expense/submit", personal).await.status,
+                StatusCode::OK
+            );
+        }
+
+        let expenses = |audit: serde_json::Value| {
+            audit["events"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|e| e["entity"] == "Expense")
+                .count()
+        };
+        assert_eq!(expenses(ale.get("/audit").await.json()), 5);
+        assert_eq!(expenses(lu.get("/audit").await.json()), 0);
+
+        let splitrecc = "/expense/splitrecc/Ale/Market";
+        assert_eq!(ale.get(splitrecc).await.json(), "Personal");
+        assert_eq!(lu.get(splitrecc).await.json(), serde_json::Value::Null);
+
+        let unusual = "/expense/unusual/Ale/Market/1000";
+        assert_eq!(ale.get(unusual).await.json()["unusual"], false);
+        assert_eq!(lu.get(unusual).await.json(), serde_json::Value::Null);
+
+        let deliveries = sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM webhook_deliveries")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(deliveries, 0);
+    }
+
+    #[sqlx::test]
+    async fn splits_by_income_in_whole_cents(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, mut lu) = test::sessions(&app).await;
+
+        for (client, amount) in [(&mut ale, 300000), (&mut lu, 100001)] {
+            let income = json!({ "month": "2026-10-01", "amount": amount });
+            assert_eq!(client.post("/income", income).await.status, StatusCode::OK);
+        }
+
+        let expense = json!({
+            "payer": "Ale",
+            "split": "ProportionalIncome",
+            "label": "Market",
+            "date": "2026-10-10",
+            "paid": 1001,
+        });
+        assert_eq!(
+            ale.post("/expense/submit", expense).await.status,
+            StatusCode::OK
+        );
+
+        let list = lu.post("/list", json!({})).await.json();
+        assert_eq!(list["pendings"][0]["c"]["ratio"], 0.250002);
+        assert_eq!(list["pendings"][0]["c"]["spent"], 250);
+    }
+}
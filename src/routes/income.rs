@@ -0,0 +1,54 @@
+use super::{list::date_to_string, Db};
+use crate::{auth::Session, queries::Person};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+pub struct Income {
+    who: Person,
+    month: String,
+    amount: i64,
+}
+
+pub async fn list(db: Db, _s: Session) -> Result<Json<Vec<Income>>, StatusCode> {
+    match crate::queries::income::all(db.deref()).await {
+        Ok(incomes) => Ok(Json(
+            incomes
+                .into_iter()
+                .map(|i| Income {
+                    who: i.who,
+                    month: date_to_string(i.month),
+                    amount: i.amount.0,
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetRequest {
+    month: String,
+    amount: i64,
+}
+
+/// Records the caller's income from the month of `month` on, until a later one replaces it.
+pub async fn set(db: Db, s: Session, r: Json<SetRequest>) -> StatusCode {
+    let month = match time::Date::parse(&r.month, &Iso8601::DEFAULT) {
+        Ok(month) if r.amount >= 0 => month,
+        _ => return StatusCode::BAD_REQUEST,
+    };
+
+    match crate::queries::income::set(db.deref(), s.who, month, r.amount).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -23,6 +23,12 @@ struct Expense {
     spent: i64,
     confirmed: bool,
     refused: bool,
+    unusual: bool,
+    ratio: Option<f64>,
+    currency: String,
+    original_paid: Option<i64>,
+    original_owed: Option<i64>,
+    exchange_rate: Option<f64>,
 }
 
 #[derive(Serialize)]
@@ -33,6 +39,9 @@ struct Transfer {
     amount: i64,
     confirmed: bool,
     refused: bool,
+    currency: String,
+    original_amount: Option<i64>,
+    exchange_rate: Option<f64>,
 }
 
 #[derive(Serialize)]
@@ -76,12 +85,11 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
+    expenses.retain(|e| !matches!(e.split, Split::Personal) || e.creator == s.who);
+
     if let Some(labels) = &f.labels {
         transfers.clear();
-        expenses = expenses
-            .into_iter()
-            .filter(|e| labels.contains(&e.label))
-            .collect();
+        expenses.retain(|e| labels.contains(&e.label));
     }
 
     let expenses = expenses.into_iter().map(|e| {
@@ -95,7 +103,10 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
             e.date,
             e.created_at,
             spent,
-            e.paid.0,
+            match e.split {
+                Split::Personal => 0,
+                _ => e.paid.0,
+            },
             e.confirmed_at.is_none() && e.refused_at.is_none(),
             e.confirmed_at.is_some(),
             Item::Expense(Expense {
@@ -110,6 +121,12 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
                 spent,
                 confirmed: e.confirmed_at.is_some(),
                 refused: e.refused_at.is_some(),
+                unusual: e.unusual,
+                ratio: e.ratio,
+                currency: e.currency,
+                original_paid: e.original_paid,
+                original_owed: e.original_owed,
+                exchange_rate: e.exchange_rate,
             }),
         )
     });
@@ -129,6 +146,9 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
                 amount: t.amount.0,
                 confirmed: t.confirmed_at.is_some(),
                 refused: t.refused_at.is_some(),
+                currency: t.currency,
+                original_amount: t.original_amount,
+                exchange_rate: t.exchange_rate,
             }),
         )
     });
@@ -137,7 +157,7 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
         .chain(transfers)
         .sorted_by_key(|a| (a.0, a.1))
         .rev()
-        .group_by(|a| (a.0.year() * 12 + a.0.month() as i32 - 1));
+        .group_by(|a| a.0.year() * 12 + a.0.month() as i32 - 1);
 
     let mut pendings = Vec::new();
     let mut months = Vec::new();
@@ -172,7 +192,7 @@ pub async fn generate(db: Db, s: Session, f: Json<Filter>) -> Result<Json<Respon
     Ok(Json(Response { pendings, months }))
 }
 
-fn date_to_string(date: time::Date) -> String {
+pub(super) fn date_to_string(date: time::Date) -> String {
     format!(
         "{:0>4}-{:0>2}-{:0>2}",
         date.year(),
@@ -0,0 +1,446 @@
+use super::Db;
+use crate::{
+    auth::{Meta, Session, SessionAsk},
+    queries::{event::Entity, session::SessionState},
+};
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use futures::TryFutureExt;
+use std::{ops::Deref, sync::Arc};
+use webauthn_rs::{
+    prelude::{
+        CreationChallengeResponse, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+        RegisterPublicKeyCredential, RequestChallengeResponse,
+    },
+    Webauthn,
+};
+
+pub struct Wa(Arc<Webauthn>);
+
+#[axum::async_trait]
+impl FromRequestParts<super::State> for Wa {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        _parts: &mut Parts,
+        state: &super::State,
+    ) -> Result<Self, Self::Rejection> {
+        Option::<Arc<Webauthn>>::from_ref(state)
+            .map(Wa)
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+}
+
+pub async fn register_start(
+    db: Db,
+    Wa(webauthn): Wa,
+    s: Session,
+) -> Result<Json<CreationChallengeResponse>, StatusCode> {
+    let exclude = crate::queries::passkey::all(db.deref(), s.who)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .map(|(_, p)| p.cred_id().clone())
+        .collect();
+
+    let (handle, name) = crate::auth::user_handle(s.who);
+    let (challenge, state) = webauthn
+        .start_passkey_registration(handle, name, name, Some(exclude))
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match crate::queries::passkey::begin(db.deref(), s.id, &state).await {
+        Ok(()) => Ok(Json(challenge)),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn register_finish(
+    db: Db,
+    Wa(webauthn): Wa,
+    s: Session,
+    meta: Meta,
+    r: Json<RegisterPublicKeyCredential>,
+) -> StatusCode {
+    let res = db.begin().and_then(|mut transaction| async move {
+        let state =
+            match crate::queries::passkey::finish::<PasskeyRegistration>(&mut transaction, s.id)
+                .await?
+            {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+
+        // The ceremony is spent even when it fails, so its challenge can't be tried again.
+        let passkey = match webauthn.finish_passkey_registration(&r, &state) {
+            Ok(passkey) => passkey,
+            Err(_) => return transaction.commit().await.map(|()| None),
+        };
+
+        let id = crate::queries::passkey::register(&mut transaction, s.who, &passkey).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Passkey,
+            id,
+            None,
+            "Registered",
+            &meta,
+        )
+        .await?;
+
+        transaction.commit().await.map(Some)
+    });
+
+    match res.await {
+        Ok(Some(())) => StatusCode::OK,
+        Ok(None) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub async fn login_start(
+    db: Db,
+    Wa(webauthn): Wa,
+    SessionAsk(id): SessionAsk,
+) -> Result<Json<RequestChallengeResponse>, StatusCode> {
+    let res = db.begin().and_then(|mut transaction| async move {
+        let who = match crate::queries::session::state(&mut transaction, id).await? {
+            Some((who, SessionState::Confirmable)) => who,
+            _ => return Ok(None),
+        };
+
+        let passkeys = crate::queries::passkey::all(&mut transaction, who)
+            .await?
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect::<Vec<_>>();
+
+        let (challenge, state) = match webauthn.start_passkey_authentication(&passkeys) {
+            Ok(started) => started,
+            Err(_) => return Ok(None),
+        };
+
+        crate::queries::passkey::begin(&mut transaction, id, &state).await?;
+        transaction.commit().await.map(|()| Some(challenge))
+    });
+
+    match res.await {
+        Ok(Some(challenge)) => Ok(Json(challenge)),
+        Ok(None) => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn login_finish(
+    db: Db,
+    Wa(webauthn): Wa,
+    SessionAsk(id): SessionAsk,
+    meta: Meta,
+    r: Json<PublicKeyCredential>,
+) -> StatusCode {
+    let res = db.begin().and_then(|mut transaction| async move {
+        let who = match crate::queries::session::state(&mut transaction, id).await? {
+            Some((who, SessionState::Confirmable)) => who,
+            _ => return Ok(None),
+        };
+
+        let state =
+            match crate::queries::passkey::finish::<PasskeyAuthentication>(&mut transaction, id)
+                .await?
+            {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+
+        // Like registration, a failed attempt still spends the ceremony.
+        let result = match webauthn.finish_passkey_authentication(&r, &state) {
+            Ok(result) => result,
+            Err(_) => return transaction.commit().await.map(|()| None),
+        };
+
+        let passkey = crate::queries::passkey::all(&mut transaction, who)
+            .await?
+            .into_iter()
+            .find(|(_, p)| p.cred_id() == result.cred_id());
+
+        let (passkey_id, mut passkey) = match passkey {
+            Some(passkey) => passkey,
+            None => return transaction.commit().await.map(|()| None),
+        };
+
+        if passkey.update_credential(&result) == Some(true) {
+            crate::queries::passkey::update(&mut transaction, passkey_id, &passkey).await?;
+        }
+
+        crate::queries::session::confirm(&mut transaction, id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(who),
+            Some(id),
+            Entity::Session,
+            id,
+            Some("Confirmable"),
+            "Convertable",
+            &meta,
+        )
+        .await?;
+
+        transaction.commit().await.map(Some)
+    });
+
+    match res.await {
+        Ok(Some(())) => StatusCode::OK,
+        Ok(None) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::{self, Client, ORIGIN};
+    use axum::http::StatusCode;
+    use openssl::{
+        bn::BigNumContext,
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::{PKey, Private},
+        sign::Signer,
+    };
+    use serde_cbor_2::Value as Cbor;
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    /// A software passkey: a P-256 key pair that answers ceremonies like a platform
+    /// authenticator would, user verification included, with `none` attestation.
+    struct Authenticator {
+        key: PKey<Private>,
+        credential_id: Vec<u8>,
+        counter: u32,
+    }
+
+    fn b64(bytes: &[u8]) -> String {
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    impl Authenticator {
+        fn new() -> Self {
+            let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+
+            Authenticator {
+                key: PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap(),
+                credential_id: rand::random::<[u8; 16]>().to_vec(),
+                counter: 0,
+            }
+        }
+
+        fn client_data(kind: &str, challenge: &Value) -> Vec<u8> {
+            json!({
+                "type": kind,
+                "challenge": challenge.as_str().unwrap(),
+                "origin": ORIGIN,
+                "crossOrigin": false,
+            })
+            .to_string()
+            .into_bytes()
+        }
+
+        /// Up, verified, and optionally carrying the attested credential.
+        fn auth_data(&mut self, rp_id: &str, attested: Option<Vec<u8>>) -> Vec<u8> {
+            self.counter += 1;
+
+            let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            data.push(if attested.is_some() { 0x45 } else { 0x05 });
+            data.extend(self.counter.to_be_bytes());
+            data.extend(attested.unwrap_or_default());
+            data
+        }
+
+        fn cose_key(&self) -> Vec<u8> {
+            let ec = self.key.ec_key().unwrap();
+            let mut ctx = BigNumContext::new().unwrap();
+            let (mut x, mut y) = (
+                openssl::bn::BigNum::new().unwrap(),
+                openssl::bn::BigNum::new().unwrap(),
+            );
+            ec.public_key()
+                .affine_coordinates(ec.group(), &mut x, &mut y, &mut ctx)
+                .unwrap();
+
+            let key = BTreeMap::from([
+                (Cbor::Integer(1), Cbor::Integer(2)),
+                (Cbor::Integer(3), Cbor::Integer(-7)),
+                (Cbor::Integer(-1), Cbor::Integer(1)),
+                (Cbor::Integer(-2), Cbor::Bytes(x.to_vec_padded(32).unwrap())),
+                (Cbor::Integer(-3), Cbor::Bytes(y.to_vec_padded(32).unwrap())),
+            ]);
+
+            serde_cbor_2::to_vec(&Cbor::Map(key)).unwrap()
+        }
+
+        fn register(&mut self, challenge: &Value) -> Value {
+            let options = &challenge["publicKey"];
+            let client_data = Self::client_data("webauthn.create", &options["challenge"]);
+
+            let mut attested = vec![0; 16];
+            attested.extend((self.credential_id.len() as u16).to_be_bytes());
+            attested.extend(&self.credential_id);
+            attested.extend(self.cose_key());
+
+            let auth_data = self.auth_data(options["rp"]["id"].as_str().unwrap(), Some(attested));
+
+            let attestation = Cbor::Map(BTreeMap::from([
+                (Cbor::Text("fmt".into()), Cbor::Text("none".into())),
+                (Cbor::Text("attStmt".into()), Cbor::Map(BTreeMap::new())),
+                (Cbor::Text("authData".into()), Cbor::Bytes(auth_data)),
+            ]));
+
+            json!({
+                "id": b64(&self.credential_id),
+                "rawId": b64(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "attestationObject": b64(&serde_cbor_2::to_vec(&attestation).unwrap()),
+                    "clientDataJSON": b64(&client_data),
+                },
+            })
+        }
+
+        fn login(&mut self, challenge: &Value) -> Value {
+            let options = &challenge["publicKey"];
+            let client_data = Self::client_data("webauthn.get", &options["challenge"]);
+            let auth_data = self.auth_data(options["rpId"].as_str().unwrap(), None);
+
+            let mut signer = Signer::new(MessageDigest::sha256(), &self.key).unwrap();
+            signer.update(&auth_data).unwrap();
+            signer.update(&Sha256::digest(&client_data)).unwrap();
+
+            json!({
+                "id": b64(&self.credential_id),
+                "rawId": b64(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "authenticatorData": b64(&auth_data),
+                    "clientDataJSON": b64(&client_data),
+                    "signature": b64(&signer.sign_to_vec().unwrap()),
+                    "userHandle": null,
+                },
+            })
+        }
+    }
+
+    async fn registered(ale: &mut Client) -> Authenticator {
+        let mut authenticator = Authenticator::new();
+
+        let challenge = ale
+            .post("/passkey/register/start", Value::Null)
+            .await
+            .json();
+        let credential = authenticator.register(&challenge);
+        let finish = ale.post("/passkey/register/finish", credential).await;
+        assert_eq!(finish.status, StatusCode::OK);
+
+        authenticator
+    }
+
+    #[sqlx::test]
+    async fn registers_and_confirms_an_ask(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+        let mut authenticator = registered(&mut ale).await;
+
+        let mut device = Client::new(&app);
+        device.post("/session/ask/Ale", Value::Null).await;
+
+        let challenge = device
+            .post("/passkey/login/start", Value::Null)
+            .await
+            .json();
+        let assertion = authenticator.login(&challenge);
+        let finish = device.post("/passkey/login/finish", assertion).await;
+        assert_eq!(finish.status, StatusCode::OK);
+
+        assert_eq!(
+            device.get("/session/state").await.json(),
+            json!("Convertable")
+        );
+        let convert = device.post("/session/convert", Value::Null).await;
+        assert_eq!(convert.status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn failed_login_spends_the_challenge(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+        let mut authenticator = registered(&mut ale).await;
+
+        let mut device = Client::new(&app);
+        device.post("/session/ask/Ale", Value::Null).await;
+
+        let challenge = device
+            .post("/passkey/login/start", Value::Null)
+            .await
+            .json();
+        let mut forged = authenticator.login(&challenge);
+        forged["response"]["signature"] = json!(b64(&[0x30, 0x06, 2, 1, 1, 2, 1, 1]));
+        let finish = device.post("/passkey/login/finish", forged).await;
+        assert_eq!(finish.status, StatusCode::BAD_REQUEST);
+
+        let assertion = authenticator.login(&challenge);
+        let replay = device.post("/passkey/login/finish", assertion).await;
+        assert_eq!(replay.status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            device.get("/session/state").await.json(),
+            json!("Confirmable")
+        );
+    }
+
+    #[sqlx::test]
+    async fn failed_registration_spends_the_challenge(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+        let mut authenticator = Authenticator::new();
+
+        let challenge = ale
+            .post("/passkey/register/start", Value::Null)
+            .await
+            .json();
+        let mut wrong = challenge.clone();
+        wrong["publicKey"]["challenge"] = json!(b64(b"not the challenge"));
+        let finish = ale
+            .post("/passkey/register/finish", authenticator.register(&wrong))
+            .await;
+        assert_eq!(finish.status, StatusCode::BAD_REQUEST);
+
+        let retry = ale
+            .post(
+                "/passkey/register/finish",
+                authenticator.register(&challenge),
+            )
+            .await;
+        assert_eq!(retry.status, StatusCode::BAD_REQUEST);
+    }
+}
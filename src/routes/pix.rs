@@ -0,0 +1,129 @@
+use super::Db;
+use crate::auth::Session;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::TryFutureExt;
+use qrcode::{render::svg, QrCode};
+use serde::{Deserialize, Serialize};
+use std::{io::Cursor, ops::Deref};
+
+#[derive(Serialize, Deserialize)]
+pub struct Key {
+    key: String,
+    name: String,
+    city: String,
+}
+
+pub async fn get_key(db: Db, s: Session) -> Result<Json<Option<Key>>, StatusCode> {
+    match crate::queries::pix::get(db.deref(), s.who).await {
+        Ok(k) => Ok(Json(k.map(|k| Key {
+            key: k.key,
+            name: k.name,
+            city: k.city,
+        }))),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Name and city go into the BR Code as is, so they must be plain ASCII within EMV limits.
+pub async fn set_key(db: Db, s: Session, r: Json<Key>) -> StatusCode {
+    let valid = |v: &str, max: usize| !v.is_empty() && v.len() <= max && v.is_ascii();
+
+    if !(valid(&r.key, 77) && valid(&r.name, 25) && valid(&r.city, 15)) {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    match crate::queries::pix::set(db.deref(), s.who, &r.key, &r.name, &r.city).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// The BR Code paying a transfer into its receiver's Pix key, if they registered one.
+pub async fn transfer(
+    db: Db,
+    _s: Session,
+    Path(id): Path<i32>,
+) -> Result<Json<String>, StatusCode> {
+    payload(db, id).await.map(Json)
+}
+
+pub async fn transfer_svg(
+    db: Db,
+    _s: Session,
+    Path(id): Path<i32>,
+) -> Result<Response, StatusCode> {
+    let code = qr(&payload(db, id).await?)?;
+    let image = code.render::<svg::Color>().min_dimensions(256, 256).build();
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], image).into_response())
+}
+
+pub async fn transfer_png(
+    db: Db,
+    _s: Session,
+    Path(id): Path<i32>,
+) -> Result<Response, StatusCode> {
+    let code = qr(&payload(db, id).await?)?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(256, 256)
+        .build();
+
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], png.into_inner()).into_response())
+}
+
+async fn payload(db: Db, id: i32) -> Result<String, StatusCode> {
+    let res = db
+        .begin()
+        .and_then(|mut transaction| async move {
+            let transfer = match crate::queries::transfer::get(&mut transaction, id).await? {
+                Some(t) => t,
+                None => return Ok(None),
+            };
+
+            let key = crate::queries::pix::get(&mut transaction, transfer.receiver).await?;
+            Ok(key.map(|k| (k, transfer)))
+        })
+        .await;
+
+    match res {
+        Ok(Some((key, t))) => {
+            crate::pix::payload(&key, t.amount.0, &txid(t.id)).ok_or(StatusCode::BAD_REQUEST)
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn txid(transfer_id: i32) -> String {
+    format!("EXPENSAS{transfer_id}")
+}
+
+fn qr(payload: &str) -> Result<QrCode, StatusCode> {
+    QrCode::new(payload).map_err(|e| {
+        tracing::error!("{e:?}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
@@ -0,0 +1,54 @@
+use super::Db;
+use crate::auth::Session;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+use std::ops::Deref;
+
+pub async fn key(State(key): State<Option<String>>) -> Result<Json<String>, StatusCode> {
+    key.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+pub struct Keys {
+    p256dh: String,
+    auth: String,
+}
+
+#[derive(Deserialize)]
+pub struct SubscribeRequest {
+    endpoint: String,
+    keys: Keys,
+}
+
+pub async fn subscribe(db: Db, s: Session, r: Json<SubscribeRequest>) -> StatusCode {
+    match crate::queries::push::subscribe(
+        db.deref(),
+        s.id,
+        &r.endpoint,
+        &r.keys.p256dh,
+        &r.keys.auth,
+    )
+    .await
+    {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeRequest {
+    endpoint: String,
+}
+
+pub async fn unsubscribe(db: Db, s: Session, r: Json<UnsubscribeRequest>) -> StatusCode {
+    match crate::queries::push::unsubscribe(db.deref(), s.id, Some(&r.endpoint)).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::{list::date_to_string, Db};
+use crate::{auth::Session, exchange, queries::rate::BASE};
+use axum::{http::StatusCode, Json};
+use futures::TryFutureExt;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+pub struct Rate {
+    currency: String,
+    day: String,
+    rate: String,
+}
+
+pub async fn list(db: Db, _s: Session) -> Result<Json<Vec<Rate>>, StatusCode> {
+    match crate::queries::rate::all(db.deref()).await {
+        Ok(rates) => Ok(Json(
+            rates
+                .into_iter()
+                .map(|r| Rate {
+                    currency: r.currency,
+                    day: date_to_string(r.day),
+                    rate: r.rate,
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetRequest {
+    currency: String,
+    day: String,
+    rate: String,
+}
+
+/// Records how many `BASE` one unit of `currency` was worth on `day`.
+/// The rate is a decimal string, so it's kept exactly as written.
+pub async fn set(db: Db, _s: Session, r: Json<SetRequest>) -> StatusCode {
+    let Some((currency, day, rate)) = parse(&r.currency, &r.day, &r.rate) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    match crate::queries::rate::set(db.deref(), currency, day, rate).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Takes `currency,day,rate` lines, with or without that header, all or nothing.
+/// Answers how many rates were recorded.
+pub async fn import(db: Db, _s: Session, csv: String) -> Result<Json<usize>, StatusCode> {
+    let rates = csv
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(n, l)| !l.is_empty() && (*n > 0 || !l.eq_ignore_ascii_case("currency,day,rate")))
+        .map(|(_, l)| {
+            let mut fields = l.split(',').map(str::trim);
+            let currency = fields.next()?;
+            let day = fields.next()?;
+            let rate = fields.next()?;
+            fields.next().is_none().then_some(())?;
+            parse(currency, day, rate)
+        })
+        .collect::<Option<Vec<_>>>();
+
+    let Some(rates) = rates else {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    };
+
+    let res = db.begin().and_then(|mut transaction| async move {
+        for &(currency, day, rate) in &rates {
+            crate::queries::rate::set(&mut transaction, currency, day, rate).await?;
+        }
+
+        transaction.commit().await.map(|()| rates.len())
+    });
+
+    match res.await {
+        Ok(count) => Ok(Json(count)),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn parse<'a>(
+    currency: &'a str,
+    day: &str,
+    rate: &str,
+) -> Option<(&'a str, time::Date, exchange::Rate)> {
+    let currency = foreign(currency)?;
+    let day = time::Date::parse(day, &Iso8601::DEFAULT).ok()?;
+    Some((currency, day, rate.parse().ok()?))
+}
+
+/// A currency code other than `BASE`, as rates are kept for.
+pub(super) fn foreign(currency: &str) -> Option<&str> {
+    (currency.len() == 3 && currency.bytes().all(|b| b.is_ascii_uppercase()) && currency != BASE)
+        .then_some(currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+    use axum::{
+        body::Body,
+        http::{header, Method, StatusCode},
+    };
+    use serde_json::json;
+
+    async fn base(db: &sqlx::PgPool, table: &str, id: i32) -> (i64, i64, String) {
+        let columns = match table {
+            "expenses" => "cents(paid), cents(owed)",
+            _ => "cents(amount), 0::BIGINT",
+        };
+
+        sqlx::query_as(&format!(
+            "SELECT {columns}, exchange_rate::TEXT FROM {table} WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn keeps_rates_as_written(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let rate = json!({ "currency": "USD", "day": "2026-10-01", "rate": "5.12345678" });
+        assert_eq!(ale.post("/rates", rate).await.status, StatusCode::OK);
+        assert_eq!(ale.get("/rates").await.json()[0]["rate"], "5.12345678");
+
+        let rate = json!({ "currency": "USD", "day": "2026-10-01", "rate": 5.1 });
+        assert_ne!(ale.post("/rates", rate).await.status, StatusCode::OK);
+
+        let csv = |ale: &test::Client, csv: &'static str| {
+            ale.request(Method::POST, "/rates/import")
+                .header(header::CONTENT_TYPE, "text/csv")
+                .body(Body::from(csv))
+                .unwrap()
+        };
+        let bad = csv(
+            &ale,
+            "currency,day,rate\nEUR,2026-10-01,6.1\nEUR,2026-10-02,1e3\n",
+        );
+        assert_eq!(ale.send(bad).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let good = csv(&ale, "EUR,2026-10-01,6.1\nEUR,2026-10-02,6.00000001\n");
+        assert_eq!(ale.send(good).await.json(), 2);
+    }
+
+    #[sqlx::test]
+    async fn converts_again_when_confirmed(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, mut lu) = test::sessions(&app).await;
+
+        let rate = json!({ "currency": "USD", "day": "2026-10-01", "rate": "5.12345678" });
+        assert_eq!(ale.post("/rates", rate).await.status, StatusCode::OK);
+
+        let expense = |date: &str| {
+            json!({
+                "payer": "Ale",
+                "split": "Arbitrary",
+                "label": "Leisure",
+                "date": date,
+                "paid": 1000,
+                "owed": 300,
+                "currency": "USD",
+            })
+        };
+
+        let stale = ale.post("/expense/submit", expense("2026-10-09")).await;
+        assert_eq!(stale.status, StatusCode::BAD_REQUEST);
+
+        let id = ale
+            .post("/expense/submit", expense("2026-10-05"))
+            .await
+            .json()["id"]
+            .as_i64()
+            .unwrap() as i32;
+        assert_eq!(
+            base(&db, "expenses", id).await,
+            (5123, 1537, String::from("5.12345678"))
+        );
+
+        let transfer = json!({ "date": "2026-10-05", "amount": 100, "currency": "USD" });
+        assert_eq!(
+            ale.post("/transfer/submit", transfer).await.status,
+            StatusCode::OK
+        );
+        let transfer = sqlx::query_scalar("SELECT MAX(id) FROM transfers")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(
+            base(&db, "transfers", transfer).await,
+            (512, 0, String::from("5.12345678"))
+        );
+
+        let rate = json!({ "currency": "USD", "day": "2026-10-04", "rate": "6" });
+        assert_eq!(ale.post("/rates", rate).await.status, StatusCode::OK);
+
+        let confirm = lu
+            .post(&format!("/expense/confirm/{id}"), json!(null))
+            .await;
+        assert_eq!(confirm.status, StatusCode::OK);
+        assert_eq!(
+            base(&db, "expenses", id).await,
+            (6000, 1800, String::from("6.00000000"))
+        );
+
+        let confirm = lu
+            .post(&format!("/transfer/confirm/{transfer}"), json!(null))
+            .await;
+        assert_eq!(confirm.status, StatusCode::OK);
+        assert_eq!(
+            base(&db, "transfers", transfer).await,
+            (600, 0, String::from("6.00000000"))
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use super::{list::date_to_string, Db};
+use crate::{
+    auth::Session,
+    queries::{Label, Person},
+};
+use axum::{extract::Path, http::StatusCode, Json};
+use futures::TryFutureExt;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Item {
+    n: i32,
+    description: String,
+    quantity: f64,
+    unit: String,
+    unit_price: i64,
+    total: i64,
+}
+
+/// What `/expense/submit` should be prefilled with, passing `receipt` along to link both.
+#[derive(Serialize)]
+pub struct Draft {
+    receipt: i32,
+    label: Label,
+    detail: String,
+    date: String,
+    paid: i64,
+    expense: Option<i32>,
+    items: Vec<Item>,
+}
+
+pub async fn import(db: Db, s: Session, xml: String) -> Result<Json<Draft>, StatusCode> {
+    let Some(nfce) = crate::nfce::parse(&xml) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let res = db.begin().and_then(|mut transaction| async move {
+        let id = crate::queries::receipt::import(&mut transaction, s.who, &nfce).await?;
+        let draft = draft(&mut transaction, s.who, id).await?;
+        transaction.commit().await.map(|()| draft)
+    });
+
+    match res.await {
+        Ok(Some(draft)) => Ok(Json(draft)),
+        Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn get(db: Db, s: Session, Path(id): Path<i32>) -> Result<Json<Draft>, StatusCode> {
+    let res = db
+        .begin()
+        .and_then(|mut transaction| async move { draft(&mut transaction, s.who, id).await });
+
+    match res.await {
+        Ok(Some(draft)) => Ok(Json(draft)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn draft(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    viewer: Person,
+    id: i32,
+) -> sqlx::Result<Option<Draft>> {
+    let Some(receipt) = crate::queries::receipt::get(&mut *transaction, viewer, id).await? else {
+        return Ok(None);
+    };
+
+    let items = crate::queries::receipt::items(&mut *transaction, viewer, id).await?;
+
+    Ok(Some(Draft {
+        receipt: receipt.id,
+        label: Label::Market,
+        detail: receipt.emitter,
+        date: date_to_string(receipt.date),
+        paid: receipt.total.0,
+        expense: receipt.expense_id,
+        items: items
+            .into_iter()
+            .map(|i| Item {
+                n: i.n,
+                description: i.description,
+                quantity: i.quantity,
+                unit: i.unit,
+                unit_price: i.unit_price.0,
+                total: i.total.0,
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+    use axum::{
+        body::Body,
+        http::{header, Method, StatusCode},
+    };
+    use serde_json::json;
+
+    fn import(client: &test::Client, xml: &'static str) -> axum::http::Request<Body> {
+        client
+            .request(Method::POST, "/receipt/import")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(xml))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn keeps_item_amounts_in_cents(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let xml = include_str!("../../fixtures/nfce-proc.xml");
+        let draft = ale.send(import(&ale, xml)).await;
+        assert_eq!(draft.status, StatusCode::OK);
+
+        let draft = draft.json();
+        assert_eq!(draft["paid"], 2554);
+        assert_eq!(draft["items"][1]["unit_price"], 598);
+        assert_eq!(draft["items"][1]["total"], 250);
+        assert_eq!(draft["items"][2]["total"], 5);
+
+        let again = ale.send(import(&ale, xml)).await.json();
+        assert_eq!(again["receipt"], draft["receipt"]);
+    }
+
+    #[sqlx::test]
+    async fn keeps_personal_receipts_out_of_the_others_sight(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, mut lu) = test::sessions(&app).await;
+
+        let xml = include_str!("../../fixtures/nfce-bare.xml");
+        let receipt = ale.send(import(&ale, xml)).await.json()["receipt"].clone();
+
+        let personal = json!({
+            "payer": "Ale",
+            "split": "Personal",
+            "label": "Market",
+            "date": "2026-09-30",
+            "paid": 1050,
+            "receipt": receipt,
+        });
+        assert_eq!(
+            ale.post("/expense/submit", personal).await.status,
+            StatusCode::OK
+        );
+
+        let path = format!("/receipt/{receipt}");
+        assert_eq!(ale.get(&path).await.status, StatusCode::OK);
+        assert_eq!(lu.get(&path).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn refuses_a_total_too_large(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let xml = include_str!("../../fixtures/nfce-overflow.xml");
+        assert_eq!(
+            ale.send(import(&ale, xml)).await.status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
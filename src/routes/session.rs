@@ -1,20 +1,63 @@
 use super::Db;
 use crate::{
-    auth::{Session, SessionAsk},
-    queries::{session::SessionState, Person},
+    auth::{Meta, Session, SessionAsk},
+    limit::Lockout,
+    notify::Notification,
+    queries::{event::Entity, session::SessionState, Person},
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::StatusCode,
+    response::Response,
+    Json,
 };
-use axum::{extract::Path, http::StatusCode, Json};
 use axum_extra::extract::PrivateCookieJar;
 use futures::TryFutureExt;
 use std::ops::Deref;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 pub async fn ask(
     db: Db,
+    State(lockout): State<Lockout>,
+    meta: Meta,
     cookies: PrivateCookieJar,
     Path(who): Path<Person>,
 ) -> Result<PrivateCookieJar, StatusCode> {
-    match crate::queries::session::ask(db.deref(), who).await {
-        Ok(id) => Ok(cookies.add(SessionAsk(id).into())),
+    let res = db.begin().and_then(|mut transaction| async move {
+        crate::queries::session::lock(&mut transaction).await?;
+
+        let recent =
+            crate::queries::session::recent(&mut transaction, who, lockout.minutes).await?;
+
+        if recent.outstanding >= lockout.ask_cap || recent.refused >= lockout.refusals {
+            return Ok(None);
+        }
+
+        let id = crate::queries::session::ask(&mut transaction, who).await?;
+
+        if let Some((_, state)) = crate::queries::session::state(&mut transaction, id).await? {
+            crate::queries::event::record(
+                &mut transaction,
+                Some(who),
+                Some(id),
+                Entity::Session,
+                id,
+                None,
+                &format!("{state:?}"),
+                &meta,
+            )
+            .await?;
+        }
+
+        transaction.commit().await.map(|()| Some(id))
+    });
+
+    match res.await {
+        Ok(Some(id)) => Ok(cookies.add(SessionAsk(id).into())),
+        Ok(None) => Err(StatusCode::TOO_MANY_REQUESTS),
         Err(e) => {
             tracing::error!("{e:?}");
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -22,8 +65,37 @@ pub async fn ask(
     }
 }
 
-pub async fn cancel(cookies: PrivateCookieJar, ask: SessionAsk) -> PrivateCookieJar {
-    cookies.remove(ask.into())
+pub async fn cancel(
+    db: Db,
+    meta: Meta,
+    cookies: PrivateCookieJar,
+    ask @ SessionAsk(id): SessionAsk,
+) -> Result<PrivateCookieJar, StatusCode> {
+    let res = db.begin().and_then(|mut transaction| async move {
+        if let Some((who, state)) = crate::queries::session::state(&mut transaction, id).await? {
+            crate::queries::event::record(
+                &mut transaction,
+                Some(who),
+                Some(id),
+                Entity::Session,
+                id,
+                Some(&format!("{state:?}")),
+                "Cancelled",
+                &meta,
+            )
+            .await?;
+        }
+
+        transaction.commit().await
+    });
+
+    match res.await {
+        Ok(()) => Ok(cookies.remove(ask.into())),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
 
 pub async fn state(
@@ -39,7 +111,74 @@ pub async fn state(
     }
 }
 
-pub async fn confirm(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
+pub async fn watch(
+    db: Db,
+    State(tx): State<broadcast::Sender<Notification>>,
+    SessionAsk(id): SessionAsk,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let rx = tx.subscribe();
+    ws.on_upgrade(move |socket| watch_socket(db.0, rx, id, socket))
+}
+
+/// Sends the ask state right away and again whenever it changes,
+/// until it leaves `Confirmable` or the device goes away.
+async fn watch_socket(
+    db: sqlx::PgPool,
+    mut rx: broadcast::Receiver<Notification>,
+    id: i32,
+    mut socket: WebSocket,
+) {
+    let mut sent = None;
+
+    loop {
+        let state = match crate::queries::session::state(&db, id).await {
+            Ok(Some((_, state))) => state,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("{e:?}");
+                return;
+            }
+        };
+
+        let text = match serde_json::to_string(&state) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::error!("{e:?}");
+                return;
+            }
+        };
+
+        if sent.as_ref() != Some(&text) {
+            if socket.send(Message::Text(text.clone())).await.is_err() {
+                return;
+            }
+            sent = Some(text);
+        }
+
+        if !matches!(state, SessionState::Confirmable) {
+            let _ = socket.close().await;
+            return;
+        }
+
+        loop {
+            tokio::select! {
+                n = rx.recv() => match n {
+                    // Missed notifications may have been about this ask, so look again.
+                    Ok(Notification { entity: Entity::Session, .. }) | Err(RecvError::Lagged(_)) => break,
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => return,
+                },
+                m = socket.recv() => match m {
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) | None => return,
+                },
+            }
+        }
+    }
+}
+
+pub async fn confirm(db: Db, s: Session, meta: Meta, Path(id): Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         match crate::queries::session::state(&mut transaction, id).await? {
             Some((who, SessionState::Confirmable)) if who != s.who => {}
@@ -47,6 +186,18 @@ pub async fn confirm(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
         };
 
         crate::queries::session::confirm(&mut transaction, id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Session,
+            id,
+            Some("Confirmable"),
+            "Convertable",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().await.map(Some)
     });
 
@@ -60,7 +211,7 @@ pub async fn confirm(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
     }
 }
 
-pub async fn refuse(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
+pub async fn refuse(db: Db, s: Session, meta: Meta, Path(id): Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         match crate::queries::session::state(&mut transaction, id).await? {
             Some((who, SessionState::Confirmable)) if who != s.who => {}
@@ -68,6 +219,18 @@ pub async fn refuse(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
         };
 
         crate::queries::session::refuse(&mut transaction, id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Session,
+            id,
+            Some("Confirmable"),
+            "Refused",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().await.map(Some)
     });
 
@@ -83,6 +246,7 @@ pub async fn refuse(db: Db, s: Session, Path(id): Path<i32>) -> StatusCode {
 
 pub async fn convert(
     db: Db,
+    meta: Meta,
     cookies: PrivateCookieJar,
     ask @ SessionAsk(id): SessionAsk,
 ) -> Result<PrivateCookieJar, StatusCode> {
@@ -93,6 +257,18 @@ pub async fn convert(
         };
 
         crate::queries::session::convert(&mut transaction, id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(who),
+            Some(id),
+            Entity::Session,
+            id,
+            Some("Convertable"),
+            "Converted",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().await.map(|()| Some(who))
     });
 
@@ -116,6 +292,80 @@ pub async fn confirmable(db: Db, s: Session) -> Result<Json<Option<i32>>, Status
     }
 }
 
-pub async fn drop(cookies: PrivateCookieJar, s: Session) -> PrivateCookieJar {
-    cookies.remove(s.into())
+pub async fn drop(
+    db: Db,
+    meta: Meta,
+    cookies: PrivateCookieJar,
+    s: Session,
+) -> Result<PrivateCookieJar, StatusCode> {
+    let res = db.begin().and_then(|mut transaction| async move {
+        crate::queries::push::unsubscribe(&mut transaction, s.id, None).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Session,
+            s.id,
+            Some("Converted"),
+            "Dropped",
+            &meta,
+        )
+        .await?;
+
+        transaction.commit().await
+    });
+
+    match res.await {
+        Ok(()) => Ok(cookies.remove(s.into())),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::{self, Client};
+    use axum::http::StatusCode;
+    use serde_json::Value;
+
+    #[sqlx::test]
+    async fn caps_asks_even_when_each_replaces_the_last(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        test::sessions(&app).await;
+        let cap = crate::env::test().ask_cap;
+
+        let mut lu = Client::new(&app);
+        for _ in 0..cap {
+            let ask = lu.post("/session/ask/Lu", Value::Null).await;
+            assert_eq!(ask.status, StatusCode::OK);
+        }
+
+        let ask = lu.post("/session/ask/Lu", Value::Null).await;
+        assert_eq!(ask.status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[sqlx::test]
+    async fn cancel_and_drop_are_audited(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let mut lu = Client::new(&app);
+        lu.post("/session/ask/Lu", Value::Null).await;
+        let cancel = lu.post("/session/cancel", Value::Null).await;
+        assert_eq!(cancel.status, StatusCode::OK);
+
+        let dropped = ale.post("/session/drop", Value::Null).await;
+        assert_eq!(dropped.status, StatusCode::OK);
+
+        let states = sqlx::query_scalar::<_, String>(
+            "SELECT COALESCE(old_state, '') || '>' || new_state FROM events ORDER BY id DESC LIMIT 2",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(states, ["Converted>Dropped", "Confirmable>Cancelled"]);
+    }
 }
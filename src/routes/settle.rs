@@ -0,0 +1,202 @@
+use super::Db;
+use crate::{
+    auth::{Meta, Session},
+    queries::{event::Entity, rate::BASE, Person},
+};
+use axum::{http::StatusCode, Json};
+use futures::TryFutureExt;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Serialize)]
+pub struct Suggestion {
+    sender: Person,
+    receiver: Person,
+    amount: i64,
+    pix: Option<String>,
+}
+
+/// Suggestions come with a BR Code for receivers that registered a Pix key.
+/// It carries no identifier, since the transfer doesn't exist yet.
+pub async fn get(db: Db, _s: Session) -> Result<Json<Vec<Suggestion>>, StatusCode> {
+    let res = db.begin().and_then(|mut transaction| async move {
+        let mut suggestions = plan(crate::queries::summary::balances(&mut transaction).await?);
+
+        for s in &mut suggestions {
+            s.pix = crate::queries::pix::get(&mut transaction, s.receiver)
+                .await?
+                .and_then(|key| crate::pix::payload(&key, s.amount, "***"));
+        }
+
+        Ok(suggestions)
+    });
+
+    match res.await {
+        Ok(suggestions) => Ok(Json(suggestions)),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Submits, as pending transfers, the suggestions the session's person is meant to send.
+/// The others are left for whoever has to send them.
+pub async fn submit(db: Db, s: Session, meta: Meta) -> Result<Json<Vec<i32>>, StatusCode> {
+    let today = OffsetDateTime::now_utc().date();
+
+    let res = db.begin().and_then(|mut transaction| async move {
+        let balances = crate::queries::summary::balances(&mut transaction).await?;
+        let mut ids = Vec::new();
+
+        for t in plan(balances).into_iter().filter(|t| t.sender == s.who) {
+            let id = crate::queries::transfer::submit(
+                &mut transaction,
+                t.sender,
+                t.receiver,
+                today,
+                t.amount,
+                BASE,
+                None,
+                None,
+            )
+            .await?;
+
+            crate::queries::event::record(
+                &mut transaction,
+                Some(s.who),
+                Some(s.id),
+                Entity::Transfer,
+                id,
+                None,
+                "Pending",
+                &meta,
+            )
+            .await?;
+
+            ids.push(id);
+        }
+
+        transaction.commit().await.map(|()| ids)
+    });
+
+    match res.await {
+        Ok(ids) => Ok(Json(ids)),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Finds the fewest transfers that zero every balance. Splitting people into as many groups
+/// summing to zero as possible is what minimizes it, since a group of k settles in k - 1.
+fn plan(balances: Vec<(Person, i64)>) -> Vec<Suggestion> {
+    let people = balances
+        .into_iter()
+        .filter(|(_, b)| *b != 0)
+        .collect::<Vec<_>>();
+
+    let n = people.len();
+    let full = (1usize << n) - 1;
+
+    let mut sums = vec![0i64; full + 1];
+    let mut groups = vec![0usize; full + 1];
+
+    for mask in 1..=full {
+        let low = mask.trailing_zeros() as usize;
+        sums[mask] = sums[mask & (mask - 1)] + people[low].1;
+
+        let best = (0..n)
+            .filter(|i| mask & (1 << i) != 0)
+            .map(|i| groups[mask ^ (1 << i)])
+            .max()
+            .unwrap_or(0);
+
+        groups[mask] = best + usize::from(sums[mask] == 0);
+    }
+
+    // Walking back through the table yields an order whose zero-sum prefixes split the groups.
+    let mut order = Vec::with_capacity(n);
+    let mut mask = full;
+    while mask != 0 {
+        let zero = usize::from(sums[mask] == 0);
+        let i = (0..n)
+            .find(|i| mask & (1 << i) != 0 && groups[mask ^ (1 << i)] + zero == groups[mask])
+            .unwrap_or(0);
+
+        order.push(i);
+        mask ^= 1 << i;
+    }
+    order.reverse();
+
+    let mut suggestions = Vec::new();
+    let mut group = Vec::new();
+    let mut sum = 0;
+
+    for i in order {
+        group.push(people[i]);
+        sum += people[i].1;
+
+        if sum == 0 {
+            settle(&mut group, &mut suggestions);
+            group.clear();
+        }
+    }
+
+    suggestions
+}
+
+/// Settles a zero-sum group in at most k - 1 transfers, since each one zeroes somebody.
+fn settle(group: &mut [(Person, i64)], suggestions: &mut Vec<Suggestion>) {
+    loop {
+        group.sort_by_key(|(_, b)| *b);
+
+        let (debtor, creditor) = match (group.first(), group.last()) {
+            (Some(&d), Some(&c)) if d.1 < 0 && c.1 > 0 => (d, c),
+            _ => return,
+        };
+
+        let amount = (-debtor.1).min(creditor.1);
+        group[0].1 += amount;
+        group[group.len() - 1].1 -= amount;
+
+        suggestions.push(Suggestion {
+            sender: debtor.0,
+            receiver: creditor.0,
+            amount,
+            pix: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+    use serde_json::json;
+    use sqlx::postgres::types::PgMoney;
+
+    #[sqlx::test]
+    async fn settles_what_the_other_person_owes(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        test::expense(&db, "Market", 1000, Some("2026-10-01")).await;
+
+        sqlx::query(
+            "
+            INSERT INTO transfers (sender, receiver, amount, date, created_at)
+            VALUES ('Lu', 'Ale', $1, '2026-10-02', NOW())
+            ",
+        )
+        .bind(PgMoney(200))
+        .execute(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            ale.get("/settle").await.json(),
+            json!([{ "sender": "Lu", "receiver": "Ale", "amount": 300, "pix": null }])
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use super::Db;
+use crate::{auth::Session, queries::Label};
+use axum::{extract::Query, http::StatusCode, Json};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+struct Month {
+    n: i32,
+    spent_me: i64,
+    spent_we: i64,
+    change_me: Option<i64>,
+    change_we: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct Row {
+    label: Label,
+    avg_me: f64,
+    avg_we: f64,
+    median_me: f64,
+    median_we: f64,
+    months: Vec<Month>,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    labels: Vec<Row>,
+}
+
+#[derive(Deserialize)]
+pub struct Range {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// The longest range asked for, in months, so a wide one can't blow the matrix up.
+const MAX_MONTHS: i32 = 120;
+
+/// The range defaults to the twelve months up to the current one, and is cut down to
+/// `MAX_MONTHS` ending at `to`.
+pub async fn get(db: Db, s: Session, r: Query<Range>) -> Result<Json<Response>, StatusCode> {
+    let parse = |date: &Option<String>| match date {
+        Some(date) => time::Date::parse(date, &Iso8601::DEFAULT)
+            .map(Some)
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        None => Ok(None),
+    };
+
+    let to = parse(&r.to)?.unwrap_or_else(|| time::OffsetDateTime::now_utc().date());
+    let from = parse(&r.from)?.unwrap_or_else(|| months_before(to, 11));
+
+    if from > to {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let from = from.max(months_before(to, MAX_MONTHS - 1));
+
+    let cells = crate::queries::stats::matrix(db.deref(), s.who, from, to)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let labels = cells
+        .into_iter()
+        .group_by(|c| c.label)
+        .into_iter()
+        .map(|(label, cells)| {
+            let cells = cells.collect::<Vec<_>>();
+
+            Row {
+                label,
+                avg_me: cells[0].avg_me,
+                avg_we: cells[0].avg_we,
+                median_me: cells[0].median_me,
+                median_we: cells[0].median_we,
+                months: cells
+                    .iter()
+                    .map(|c| Month {
+                        n: c.month.year() * 12 + c.month.month() as i32 - 1,
+                        spent_me: c.spent_me,
+                        spent_we: c.spent_we,
+                        change_me: c.change_me,
+                        change_we: c.change_we,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(Json(Response { labels }))
+}
+
+/// The first day of the month `n` months before `date`'s, or the earliest date there is.
+fn months_before(date: time::Date, n: i32) -> time::Date {
+    let months = date.year() * 12 + date.month() as i32 - 1 - n;
+
+    time::Month::try_from((months.rem_euclid(12) + 1) as u8)
+        .and_then(|month| time::Date::from_calendar_date(months.div_euclid(12), month, 1))
+        .unwrap_or(time::Date::MIN)
+}
+
+#[derive(Serialize)]
+struct Projection {
+    so_far: i64,
+    recurring: i64,
+    projected: i64,
+    low: i64,
+    high: i64,
+}
+
+impl Projection {
+    /// Bands are one standard deviation of the daily run-rate either way.
+    fn new(so_far: i64, recurring: i64, rate: f64, spread: f64, days_left: u8) -> Projection {
+        let rest =
+            |rate: f64| so_far + recurring + (rate.max(0.0) * days_left as f64).round() as i64;
+
+        Projection {
+            so_far,
+            recurring,
+            projected: rest(rate),
+            low: rest(rate - spread),
+            high: rest(rate + spread),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Outlook {
+    label: Label,
+    bills: Vec<String>,
+    spent_me: Projection,
+    spent_we: Projection,
+}
+
+#[derive(Serialize)]
+pub struct ForecastResponse {
+    days_left: u8,
+    labels: Vec<Outlook>,
+}
+
+pub async fn forecast(db: Db, s: Session) -> Result<Json<ForecastResponse>, StatusCode> {
+    let today = time::OffsetDateTime::now_utc().date();
+    let days_left = time::util::days_in_year_month(today.year(), today.month()) - today.day();
+
+    let outlooks = crate::queries::stats::outlook(db.deref(), s.who, today)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let labels = outlooks
+        .into_iter()
+        .map(|o| Outlook {
+            label: o.label,
+            bills: o.due,
+            spent_me: Projection::new(o.mtd_me, o.due_me, o.rate_me, o.spread_me, days_left),
+            spent_we: Projection::new(o.mtd_we, o.due_we, o.rate_we, o.spread_we, days_left),
+        })
+        .collect();
+
+    Ok(Json(ForecastResponse { days_left, labels }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test;
+
+    #[sqlx::test]
+    async fn totals_labels_by_month(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        for (date, paid) in [("2026-08-03", 1001), ("2026-10-05", 250)] {
+            test::expense(&db, "Market", paid, Some(date)).await;
+        }
+
+        let stats = ale.get("/stats?from=2026-08-01&to=2026-10-01").await.json();
+        let market = &stats["labels"][0];
+
+        assert_eq!(market["label"], "Market");
+        assert_eq!(market["avg_we"], 417.0);
+        assert_eq!(market["median_we"], 250.0);
+
+        let months = market["months"].as_array().unwrap();
+        let spent_we = months.iter().map(|m| &m["spent_we"]).collect::<Vec<_>>();
+        let change_we = months.iter().map(|m| &m["change_we"]).collect::<Vec<_>>();
+        assert_eq!(spent_we, [1001, 0, 250]);
+        assert_eq!(
+            change_we,
+            [&serde_json::Value::Null, &(-1001).into(), &250.into()]
+        );
+    }
+
+    #[sqlx::test]
+    async fn clamps_the_range(db: sqlx::PgPool) {
+        let app = test::app(db).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        let stats = ale.get("/stats?from=0001-01-01&to=2026-10-01").await.json();
+        let months = stats["labels"][0]["months"].as_array().unwrap();
+
+        assert_eq!(months.len(), super::MAX_MONTHS as usize);
+        assert_eq!(months[0]["n"], 2016 * 12 + 10);
+    }
+
+    #[sqlx::test]
+    async fn forecasts_from_cents(db: sqlx::PgPool) {
+        let app = test::app(db.clone()).await;
+        let (mut ale, _) = test::sessions(&app).await;
+
+        test::expense(&db, "Water", 1234, None).await;
+
+        let forecast = ale.get("/forecast").await.json();
+        let water = forecast["labels"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["label"] == "Water")
+            .unwrap();
+
+        assert_eq!(water["spent_we"]["so_far"], 1234);
+        assert_eq!(water["spent_me"]["so_far"], 617);
+    }
+}
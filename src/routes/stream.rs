@@ -0,0 +1,111 @@
+use crate::{auth::Session, notify::Notification};
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Pushes every transition made by someone else, so the frontend knows when to refetch,
+/// except those on personal expenses that aren't the viewer's.
+/// A `resync` event means some were missed and everything should be refetched.
+pub async fn events(
+    State(tx): State<broadcast::Sender<Notification>>,
+    s: Session,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = futures::stream::unfold(tx.subscribe(), move |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(n) if n.session_id == Some(s.id) => continue,
+                Ok(n) if n.private_to.is_some_and(|p| p != s.who) => continue,
+                Ok(n) => {
+                    let event = Event::default().json_data(&n).unwrap_or_default();
+                    return Some((Ok(event), rx));
+                }
+                Err(RecvError::Lagged(_)) => {
+                    return Some((Ok(Event::default().event("resync").data("")), rx));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{auth::Session, notify::Notification, queries::event::Entity, queries::Person};
+    use axum::{extract::State, response::IntoResponse};
+    use hyper::body::HttpBody;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn asks_for_a_resync_after_lagging() {
+        let (tx, _) = broadcast::channel(1);
+        let sse = super::events(
+            State(tx.clone()),
+            Session {
+                who: Person::Ale,
+                id: 1,
+            },
+        )
+        .await;
+
+        for id in 0..3 {
+            tx.send(Notification {
+                id,
+                actor: Some(Person::Lu),
+                session_id: Some(2),
+                entity: Entity::Expense,
+                entity_id: id,
+                old_state: None,
+                new_state: String::from("Pending"),
+                private_to: None,
+            })
+            .unwrap();
+        }
+
+        let mut body = sse.into_response().into_body();
+        let first = body.data().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&first).starts_with("event:resync"));
+
+        let second = body.data().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&second).contains(r#""id":2"#));
+    }
+
+    #[tokio::test]
+    async fn keeps_quiet_about_the_others_personal_expenses() {
+        let (tx, _) = broadcast::channel(4);
+        let sse = super::events(
+            State(tx.clone()),
+            Session {
+                who: Person::Ale,
+                id: 1,
+            },
+        )
+        .await;
+
+        for (id, private_to) in [(1, Some(Person::Lu)), (2, Some(Person::Ale)), (3, None)] {
+            tx.send(Notification {
+                id,
+                actor: Some(Person::Lu),
+                session_id: Some(2),
+                entity: Entity::Expense,
+                entity_id: id,
+                old_state: None,
+                new_state: String::from("Confirmed"),
+                private_to,
+            })
+            .unwrap();
+        }
+
+        let mut body = sse.into_response().into_body();
+        let first = body.data().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&first).contains(r#""id":2"#));
+
+        let second = body.data().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&second).contains(r#""id":3"#));
+    }
+}
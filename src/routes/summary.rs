@@ -1,8 +1,13 @@
 use super::Db;
-use crate::{auth::Session, queries::Person};
-use axum::{http::StatusCode, Json};
+use crate::{auth::Session, grace::Grace, queries::Person};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
 use futures::TryFutureExt;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
 
 #[derive(Serialize)]
 pub struct GetResponse {
@@ -11,15 +16,36 @@ pub struct GetResponse {
     owed_definitely: i64,
     pending_you: i64,
     pending_other: i64,
+    expiring_you: i64,
+    by_currency: Vec<CurrencyOwed>,
 }
 
-pub async fn get(db: Db, s: Session) -> Result<Json<GetResponse>, StatusCode> {
-    let (owed, resolvable) = db
+#[derive(Serialize)]
+pub struct CurrencyOwed {
+    currency: String,
+    owed_original: i64,
+    owed_base: i64,
+}
+
+pub async fn get(
+    db: Db,
+    State(grace): State<Option<Grace>>,
+    s: Session,
+) -> Result<Json<GetResponse>, StatusCode> {
+    let (owed, currencies, resolvable, expiring) = db
         .begin()
         .and_then(|mut tr| async move {
             let owed = crate::queries::summary::total_owed(&mut tr, s.who).await?;
+            let currencies = crate::queries::summary::currency_owed(&mut tr, s.who).await?;
             let resolvable = crate::queries::summary::resolvable_count(&mut tr, s.who).await?;
-            Ok((owed, resolvable))
+            let expiring = match grace {
+                Some(g) => {
+                    crate::queries::summary::expiring_count(&mut tr, s.who, g.days, g.warn_days)
+                        .await?
+                }
+                None => 0,
+            };
+            Ok((owed, currencies, resolvable, expiring))
         })
         .await
         .map_err(|e| {
@@ -33,5 +59,61 @@ pub async fn get(db: Db, s: Session) -> Result<Json<GetResponse>, StatusCode> {
         owed_definitely: owed.definitely,
         pending_you: resolvable.by_you,
         pending_other: resolvable.by_other,
+        expiring_you: expiring,
+        by_currency: currencies
+            .into_iter()
+            .map(|c| CurrencyOwed {
+                currency: c.currency,
+                owed_original: c.original,
+                owed_base: c.base,
+            })
+            .collect(),
     }))
 }
+
+#[derive(Serialize)]
+pub struct HistoryPoint {
+    period: String,
+    owed_maybe: i64,
+    owed_definitely: i64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Day,
+    Month,
+}
+
+#[derive(Deserialize)]
+pub struct HistoryFilter {
+    by: Option<Unit>,
+}
+
+pub async fn history(
+    db: Db,
+    s: Session,
+    f: Query<HistoryFilter>,
+) -> Result<Json<Vec<HistoryPoint>>, StatusCode> {
+    let unit = match f.by.unwrap_or(Unit::Month) {
+        Unit::Day => "day",
+        Unit::Month => "month",
+    };
+
+    match crate::queries::summary::history(db.deref(), s.who, unit).await {
+        Ok(points) => Ok(Json(
+            points
+                .into_iter()
+                .map(|p| HistoryPoint {
+                    period: super::list::date_to_string(p.period),
+                    owed_maybe: p.maybe,
+                    owed_definitely: p.definitely,
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
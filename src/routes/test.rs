@@ -0,0 +1,184 @@
+//! Drives the whole router in-process, against a database set up by `#[sqlx::test]`.
+
+use super::State;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use sqlx::postgres::types::PgMoney;
+use std::{collections::HashMap, net::SocketAddr};
+use tower::ServiceExt;
+
+pub const ORIGIN: &str = "https://localhost";
+
+pub async fn app(db: sqlx::PgPool) -> Router {
+    let env = crate::env::test();
+    let (notifications, _) = tokio::sync::broadcast::channel(64);
+
+    let state = State {
+        key: crate::auth::key(&env),
+        retired: crate::auth::retired_keys(&env),
+        webauthn: crate::auth::webauthn(&env),
+        limiter: crate::limit::rate_limiter(&env),
+        proxies: crate::limit::trusted_proxies(&env),
+        lockout: crate::limit::lockout(&env),
+        notifications,
+        push_key: None,
+        grace: None,
+        duplicate_days: super::expense::DuplicateDays(env.duplicate_days),
+        attachments: crate::attachment::init(&env).await.unwrap(),
+        db,
+    };
+
+    super::app(state, &env)
+}
+
+/// A browser on the allowed origin: keeps its cookies and echoes the CSRF token it was handed.
+pub struct Client {
+    app: Router,
+    pub cookies: HashMap<String, String>,
+    pub csrf: Option<String>,
+}
+
+pub struct Reply {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+impl Client {
+    pub fn new(app: &Router) -> Self {
+        Client {
+            app: app.clone(),
+            cookies: HashMap::new(),
+            csrf: None,
+        }
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> axum::http::request::Builder {
+        let cookies = self
+            .cookies
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let mut req = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::ORIGIN, ORIGIN)
+            .header(header::COOKIE, cookies)
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+
+        if let Some(token) = &self.csrf {
+            req = req.header(crate::auth::HEADER_CSRF, token);
+        }
+
+        req
+    }
+
+    pub async fn send(&mut self, req: Request<Body>) -> Reply {
+        let res = self.app.clone().oneshot(req).await.unwrap();
+
+        for cookie in res.headers().get_all(header::SET_COOKIE) {
+            let cookie = cookie.to_str().unwrap();
+            let (name, value) = cookie
+                .split(';')
+                .next()
+                .and_then(|c| c.split_once('='))
+                .unwrap();
+
+            match value {
+                "" => self.cookies.remove(name),
+                value => self.cookies.insert(name.to_owned(), value.to_owned()),
+            };
+        }
+
+        if let Some(token) = res.headers().get(crate::auth::HEADER_CSRF) {
+            self.csrf = Some(token.to_str().unwrap().to_owned());
+        }
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        Reply {
+            status,
+            headers,
+            body: body.to_vec(),
+        }
+    }
+
+    pub async fn get(&mut self, path: &str) -> Reply {
+        let req = self.request(Method::GET, path).body(Body::empty()).unwrap();
+        self.send(req).await
+    }
+
+    pub async fn post(&mut self, path: &str, json: Value) -> Reply {
+        let req = self
+            .request(Method::POST, path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string()))
+            .unwrap();
+
+        self.send(req).await
+    }
+}
+
+/// Ale's session comes first and needs no confirmation; Ale then confirms Lu's.
+pub async fn sessions(app: &Router) -> (Client, Client) {
+    let mut ale = Client::new(app);
+    assert_eq!(
+        ale.post("/session/ask/Ale", Value::Null).await.status,
+        StatusCode::OK
+    );
+    assert_eq!(
+        ale.post("/session/convert", Value::Null).await.status,
+        StatusCode::OK
+    );
+
+    let mut lu = Client::new(app);
+    assert_eq!(
+        lu.post("/session/ask/Lu", Value::Null).await.status,
+        StatusCode::OK
+    );
+
+    let id = ale.get("/session/confirmable").await.json();
+    let confirm = ale
+        .post(&format!("/session/confirm/{id}"), Value::Null)
+        .await;
+    assert_eq!(confirm.status, StatusCode::OK);
+    assert_eq!(
+        lu.post("/session/convert", Value::Null).await.status,
+        StatusCode::OK
+    );
+
+    (ale, lu)
+}
+
+/// An expense Ale paid and split evenly, already confirmed, dated today unless told otherwise.
+pub async fn expense(db: &sqlx::PgPool, label: &str, paid: i64, date: Option<&str>) -> i32 {
+    sqlx::query_scalar(
+        "
+        INSERT INTO expenses (creator, payer, split, paid, owed, label, date, created_at, confirmed_at)
+        VALUES ('Ale', 'Ale', 'Evenly', $1, $2, $3::label, COALESCE($4::DATE, CURRENT_DATE), NOW(), NOW())
+        RETURNING id
+        ",
+    )
+    .bind(PgMoney(paid))
+    .bind(PgMoney(paid / 2))
+    .bind(label)
+    .bind(date)
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
@@ -1,30 +1,74 @@
-use super::Db;
-use crate::{auth::Session, queries::Person};
+use super::{rate::foreign, Db};
+use crate::{
+    auth::{Meta, Session},
+    queries::{event::Entity, rate::BASE},
+};
 use axum::{extract::Path, http::StatusCode, Json};
 use futures::TryFutureExt;
 use serde::Deserialize;
-use std::ops::Deref;
 use time::format_description::well_known::Iso8601;
 
 #[derive(Deserialize)]
 pub struct SubmitRequest {
     date: String,
     amount: i64,
+    currency: Option<String>,
 }
 
-pub async fn submit(db: Db, s: Session, r: Json<SubmitRequest>) -> StatusCode {
+pub async fn submit(db: Db, s: Session, meta: Meta, r: Json<SubmitRequest>) -> StatusCode {
     let date = match time::Date::parse(&r.date, &Iso8601::DEFAULT) {
         Err(_) => return StatusCode::BAD_REQUEST,
         Ok(data) => data,
     };
 
-    let receiver = match s.who {
-        Person::Ale => Person::Lu,
-        Person::Lu => Person::Ale,
+    let receiver = s.who.other();
+
+    let currency = match r.currency.as_deref() {
+        None | Some(BASE) => None,
+        Some(c) => match foreign(c) {
+            Some(c) => Some(c.to_owned()),
+            None => return StatusCode::BAD_REQUEST,
+        },
     };
 
-    match crate::queries::transfer::submit(db.deref(), s.who, receiver, date, r.amount).await {
-        Ok(_) => StatusCode::OK,
+    let res = db.begin().and_then(|mut transaction| async move {
+        let (amount, original) = match &currency {
+            Some(c) => match crate::queries::rate::rate_for(&mut transaction, c, date).await? {
+                Some(rate) => (rate.convert(r.amount), Some((r.amount, rate))),
+                None => return Ok(StatusCode::BAD_REQUEST),
+            },
+            None => (r.amount, None),
+        };
+
+        let id = crate::queries::transfer::submit(
+            &mut transaction,
+            s.who,
+            receiver,
+            date,
+            amount,
+            currency.as_deref().unwrap_or(BASE),
+            original.map(|o| o.0),
+            original.map(|o| o.1),
+        )
+        .await?;
+
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Transfer,
+            id,
+            None,
+            "Pending",
+            &meta,
+        )
+        .await?;
+
+        transaction.commit().await.map(|()| StatusCode::OK)
+    });
+
+    match res.await {
+        Ok(status) => status,
         Err(e) => {
             tracing::error!("{e:?}");
             StatusCode::INTERNAL_SERVER_ERROR
@@ -32,13 +76,26 @@ pub async fn submit(db: Db, s: Session, r: Json<SubmitRequest>) -> StatusCode {
     }
 }
 
-pub async fn confirm(db: Db, s: Session, id: Path<i32>) -> StatusCode {
+pub async fn confirm(db: Db, s: Session, meta: Meta, id: Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         if !crate::queries::transfer::resolvable(&mut transaction, *id, s.who).await? {
             return Ok(None);
         };
 
         crate::queries::transfer::confirm(&mut transaction, *id, s.who).await?;
+        crate::exchange::transfer(&mut transaction, *id).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Transfer,
+            *id,
+            Some("Pending"),
+            "Confirmed",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().map_ok(Some).await
     });
 
@@ -52,13 +109,25 @@ pub async fn confirm(db: Db, s: Session, id: Path<i32>) -> StatusCode {
     }
 }
 
-pub async fn refuse(db: Db, s: Session, id: Path<i32>) -> StatusCode {
+pub async fn refuse(db: Db, s: Session, meta: Meta, id: Path<i32>) -> StatusCode {
     let res = db.begin().and_then(|mut transaction| async move {
         if !crate::queries::transfer::resolvable(&mut transaction, *id, s.who).await? {
             return Ok(None);
         };
 
         crate::queries::transfer::refuse(&mut transaction, *id, s.who).await?;
+        crate::queries::event::record(
+            &mut transaction,
+            Some(s.who),
+            Some(s.id),
+            Entity::Transfer,
+            *id,
+            Some("Pending"),
+            "Refused",
+            &meta,
+        )
+        .await?;
+
         transaction.commit().map_ok(Some).await
     });
 
@@ -0,0 +1,71 @@
+use super::Db;
+use crate::auth::Session;
+use axum::{extract::Path, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use time::format_description::well_known::Iso8601;
+
+#[derive(Serialize)]
+pub struct Webhook {
+    id: i32,
+    url: String,
+    filter: Vec<String>,
+    exhausted: i64,
+    last_error: Option<String>,
+    created_at: String,
+}
+
+pub async fn list(db: Db, _s: Session) -> Result<Json<Vec<Webhook>>, StatusCode> {
+    match crate::queries::webhook::all(db.deref(), crate::webhook::MAX_ATTEMPTS).await {
+        Ok(ws) => Ok(Json(
+            ws.into_iter()
+                .map(|w| Webhook {
+                    id: w.id,
+                    url: w.url,
+                    filter: w.filter,
+                    exhausted: w.exhausted,
+                    last_error: w.last_error,
+                    created_at: w.created_at.format(&Iso8601::DEFAULT).unwrap_or_default(),
+                })
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateRequest {
+    url: String,
+    secret: String,
+    filter: Vec<String>,
+}
+
+pub async fn create(db: Db, _s: Session, r: Json<CreateRequest>) -> Result<Json<i32>, StatusCode> {
+    let scheme = reqwest::Url::parse(&r.url).map(|u| u.scheme().to_owned());
+
+    if !matches!(scheme.as_deref(), Ok("http" | "https")) || r.secret.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match crate::queries::webhook::create(db.deref(), &r.url, &r.secret, &r.filter).await {
+        Ok(id) => Ok(Json(id)),
+        Err(e) => {
+            tracing::error!("{e:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn delete(db: Db, _s: Session, Path(id): Path<i32>) -> StatusCode {
+    match crate::queries::webhook::delete(db.deref(), id).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            tracing::error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
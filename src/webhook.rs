@@ -0,0 +1,229 @@
+use crate::notify::Notification;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const BATCH: i64 = 16;
+pub const MAX_ATTEMPTS: i32 = 12;
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries in a batch go out concurrently, so a lease only has to outlast one timeout.
+const LEASE: Duration = Duration::from_secs(60);
+
+/// Delivers the webhook outbox in the background. Rows are queued by the `events_webhooks`
+/// trigger, so the worker only needs a nudge from notifications, with a poll for retries.
+pub fn init(db: sqlx::PgPool, mut rx: broadcast::Receiver<Notification>) -> anyhow::Result<()> {
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = deliver(&db, &client).await {
+                tracing::error!("{e:?}");
+            }
+
+            tokio::select! {
+                _ = rx.recv() => {}
+                _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn deliver(db: &sqlx::PgPool, client: &reqwest::Client) -> anyhow::Result<()> {
+    let due = crate::queries::webhook::claim(db, BATCH, MAX_ATTEMPTS, LEASE.as_secs_f64()).await?;
+
+    futures::future::join_all(due.into_iter().map(|d| attempt(db, client, d)))
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Receivers check the signature over `{timestamp}.{body}` and reject stale timestamps,
+/// so a captured delivery can't be replayed later.
+async fn attempt(
+    db: &sqlx::PgPool,
+    client: &reqwest::Client,
+    d: crate::queries::webhook::Delivery,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_vec(&d.payload)?;
+    let timestamp = time::OffsetDateTime::now_utc().unix_timestamp().to_string();
+
+    let res = client
+        .post(&d.url)
+        .header("content-type", "application/json")
+        .header("x-expensas-delivery", d.id.to_string())
+        .header("x-expensas-timestamp", &timestamp)
+        .header(
+            "x-expensas-signature",
+            format!("sha256={}", sign(&d.secret, &timestamp, &body)),
+        )
+        .body(body)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+
+    match res {
+        Ok(_) => crate::queries::webhook::delivered(db, d.id).await?,
+        Err(e) => {
+            let attempts = d.attempts + 1;
+            let error = e.to_string();
+            crate::queries::webhook::failed(db, d.id, &error).await?;
+
+            if attempts >= MAX_ATTEMPTS {
+                tracing::error!(
+                    "Webhook delivery {} exhausted after {attempts} attempts: {error}",
+                    d.id
+                );
+            } else {
+                tracing::warn!(
+                    "Webhook delivery {} failed (attempt {attempts}): {error}",
+                    d.id
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deliver, MAX_ATTEMPTS};
+    use crate::queries::event::Entity;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// A receiver on a local port that records every request and answers with `status`.
+    async fn stub(status: StatusCode) -> (String, Received) {
+        let received = Received::default();
+
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        received.lock().unwrap().push((headers, body));
+                        status
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+
+        (url, received)
+    }
+
+    async fn pending(db: &sqlx::PgPool) {
+        crate::queries::event::record(
+            db,
+            None,
+            None,
+            Entity::Expense,
+            1,
+            None,
+            "Pending",
+            &serde_json::Value::Null,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn signs_the_timestamp_and_body(db: sqlx::PgPool) {
+        let (url, received) = stub(StatusCode::OK).await;
+        crate::queries::webhook::create(&db, &url, "hush", &[])
+            .await
+            .unwrap();
+        pending(&db).await;
+
+        let client = reqwest::Client::new();
+        deliver(&db, &client).await.unwrap();
+        deliver(&db, &client).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+
+        let (headers, body) = &received[0];
+        let timestamp = headers["x-expensas-timestamp"].to_str().unwrap();
+        let signature = headers["x-expensas-signature"].to_str().unwrap();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"hush").unwrap();
+        mac.update(format!("{timestamp}.{}", String::from_utf8_lossy(body)).as_bytes());
+        let expected = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+
+        assert_eq!(signature, format!("sha256={expected}"));
+
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["new_state"], "Pending");
+    }
+
+    #[sqlx::test]
+    async fn surfaces_exhausted_deliveries(db: sqlx::PgPool) {
+        let (url, received) = stub(StatusCode::BAD_GATEWAY).await;
+        crate::queries::webhook::create(&db, &url, "hush", &["Expense".to_owned()])
+            .await
+            .unwrap();
+        pending(&db).await;
+
+        sqlx::query("UPDATE webhook_deliveries SET attempts = $1")
+            .bind(MAX_ATTEMPTS - 1)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        deliver(&db, &client).await.unwrap();
+
+        let webhooks = crate::queries::webhook::all(&db, MAX_ATTEMPTS)
+            .await
+            .unwrap();
+        assert_eq!(webhooks[0].exhausted, 1);
+        assert!(webhooks[0].last_error.as_deref().unwrap().contains("502"));
+
+        sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = NOW()")
+            .execute(&db)
+            .await
+            .unwrap();
+        deliver(&db, &client).await.unwrap();
+
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+}